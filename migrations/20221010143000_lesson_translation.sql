@@ -0,0 +1,7 @@
+CREATE TABLE lesson_translation (
+    lesson_id INT NOT NULL REFERENCES lesson (id) ON DELETE CASCADE,
+    lang TEXT NOT NULL,
+    text TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (lesson_id, lang)
+);
@@ -0,0 +1,4 @@
+-- ISO 639-1 code detected by the bot, `und` if undetermined.
+-- Existing lessons are backfilled with `cargo run --release --bin detect-langs`
+ALTER TABLE lesson ADD COLUMN lang TEXT;
+CREATE INDEX lesson_lang_idx ON lesson (lang);
@@ -0,0 +1,7 @@
+-- Readers are identified by a keyed hash of their telegram ID, see `reader_key`
+CREATE TABLE bookmark (
+    reader TEXT NOT NULL,
+    lesson_id INT NOT NULL REFERENCES lesson (id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (reader, lesson_id)
+);
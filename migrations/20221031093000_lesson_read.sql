@@ -0,0 +1,9 @@
+-- Lessons shown to a reader, identified by `reader_key`, rows expire after READ_STATE_LIFETIME
+CREATE TABLE lesson_read (
+    reader TEXT NOT NULL,
+    lesson_id INT NOT NULL REFERENCES lesson (id) ON DELETE CASCADE,
+    read_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (reader, lesson_id)
+);
+
+CREATE INDEX lesson_read_read_at_idx ON lesson_read (read_at);
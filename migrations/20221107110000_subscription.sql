@@ -0,0 +1,12 @@
+-- Opt-in notifications, unlike lessons these rows identify readers by their chat ID
+CREATE TABLE subscription (
+    chat_id BIGINT NOT NULL,
+    keyword TEXT NOT NULL,
+    lang TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (chat_id, keyword)
+);
+
+ALTER TABLE lesson ADD COLUMN notified_at timestamptz;
+-- Don't notify about lessons approved before subscriptions existed
+UPDATE lesson SET notified_at = now() WHERE status >= 'approved';
@@ -0,0 +1,4 @@
+-- A moderator reviewing a new lesson, others skip it until the claim times out
+ALTER TABLE lesson
+    ADD COLUMN claimed_by BIGINT,
+    ADD COLUMN claimed_at timestamptz;
@@ -0,0 +1,10 @@
+-- Moderators' notes on lessons, never shown to readers
+CREATE TABLE lesson_note (
+    id SERIAL PRIMARY KEY,
+    lesson_id INT NOT NULL REFERENCES lesson (id) ON DELETE CASCADE,
+    author BIGINT NOT NULL,
+    author_name TEXT NOT NULL,
+    text TEXT NOT NULL,
+    created_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX lesson_note_lesson_id_idx ON lesson_note (lesson_id);
@@ -0,0 +1,13 @@
+-- Why moderators reject lessons, one of REJECTION_REASONS
+ALTER TABLE lesson ADD COLUMN reason TEXT;
+
+-- Moderation decisions to report on
+CREATE TABLE lesson_status_change (
+    id SERIAL PRIMARY KEY,
+    lesson_id INT NOT NULL REFERENCES lesson (id) ON DELETE CASCADE,
+    status lesson_status NOT NULL,
+    reason TEXT,
+    moderator BIGINT,
+    changed_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX lesson_status_change_changed_at_idx ON lesson_status_change (changed_at);
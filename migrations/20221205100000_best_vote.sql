@@ -0,0 +1,7 @@
+-- Moderators' votes to make a lesson best, see BEST_QUORUM
+CREATE TABLE best_vote (
+    lesson_id INT NOT NULL REFERENCES lesson (id) ON DELETE CASCADE,
+    moderator BIGINT NOT NULL,
+    voted_at timestamptz NOT NULL DEFAULT now(),
+    PRIMARY KEY (lesson_id, moderator)
+);
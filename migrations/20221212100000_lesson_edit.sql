@@ -0,0 +1,5 @@
+-- The submitted text of a lesson edited by a moderator, kept for auditing
+ALTER TABLE lesson
+    ADD COLUMN original_text TEXT,
+    ADD COLUMN edited_by BIGINT,
+    ADD COLUMN edited_at timestamptz;
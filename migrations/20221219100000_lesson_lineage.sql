@@ -0,0 +1,9 @@
+-- Lessons split or merged by moderators are replaced by new ones
+ALTER TYPE lesson_status ADD VALUE 'superseded' BEFORE 'rejected';
+
+CREATE TABLE lesson_lineage (
+    source_id INT NOT NULL REFERENCES lesson (id) ON DELETE CASCADE,
+    result_id INT NOT NULL REFERENCES lesson (id) ON DELETE CASCADE,
+    PRIMARY KEY (source_id, result_id)
+);
+CREATE INDEX lesson_lineage_result_id_idx ON lesson_lineage (result_id);
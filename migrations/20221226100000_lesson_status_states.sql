@@ -0,0 +1,21 @@
+-- More lifecycle states. The enum is recreated rather than extended with `ADD VALUE`,
+-- which can't be used in the transaction adding it. Allowed transitions live in
+-- `LessonStatus::transitions`, the order only ranks lessons sorted by score.
+ALTER TYPE lesson_status RENAME TO lesson_status_old;
+CREATE TYPE lesson_status AS ENUM (
+    'superseded',
+    'withdrawn',
+    'archived',
+    'duplicate',
+    'rejected',
+    'needs_edit',
+    'new',
+    'approved',
+    'best'
+);
+ALTER TABLE lesson ALTER COLUMN status DROP DEFAULT;
+ALTER TABLE lesson ALTER COLUMN status TYPE lesson_status USING status::text::lesson_status;
+ALTER TABLE lesson ALTER COLUMN status SET DEFAULT 'new';
+ALTER TABLE lesson_status_change
+    ALTER COLUMN status TYPE lesson_status USING status::text::lesson_status;
+DROP TYPE lesson_status_old;
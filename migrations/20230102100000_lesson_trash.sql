@@ -0,0 +1,15 @@
+-- Deleted lessons stay in the trash until purged after TRASH_RETENTION
+ALTER TABLE lesson ADD COLUMN deleted_at timestamptz;
+ALTER TABLE lesson ADD COLUMN deleted_by BIGINT;
+CREATE INDEX lesson_deleted_at_idx ON lesson (deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- Deletions, restores and purges, outlives the lessons to prove they are gone
+CREATE TABLE lesson_deletion (
+    id SERIAL PRIMARY KEY,
+    lesson_id INT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('delete', 'restore', 'purge')),
+    admin BIGINT,
+    reason TEXT,
+    done_at timestamptz NOT NULL DEFAULT now()
+);
+CREATE INDEX lesson_deletion_lesson_id_idx ON lesson_deletion (lesson_id);
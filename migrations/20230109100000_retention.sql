@@ -0,0 +1,2 @@
+-- Spam tokens are forgotten once they can't limit the rate anymore
+ALTER TABLE lesson ALTER COLUMN spam_token DROP NOT NULL;
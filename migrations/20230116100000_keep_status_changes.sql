@@ -0,0 +1,5 @@
+-- Status changes outlive purged lessons to keep the rejection reports complete
+ALTER TABLE lesson_status_change ALTER COLUMN lesson_id DROP NOT NULL;
+ALTER TABLE lesson_status_change DROP CONSTRAINT lesson_status_change_lesson_id_fkey;
+ALTER TABLE lesson_status_change ADD CONSTRAINT lesson_status_change_lesson_id_fkey
+    FOREIGN KEY (lesson_id) REFERENCES lesson (id) ON DELETE SET NULL;
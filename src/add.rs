@@ -1,4 +1,6 @@
-use crate::{internal_error, Replier, ReplyResult, CONF, TEXT};
+use crate::{
+    detect_lang, internal_error, moderation::post_to_moderators, Replier, ReplyResult, CONF, TEXT,
+};
 use sqlx::{query, PgPool};
 use time::OffsetDateTime;
 
@@ -21,10 +23,14 @@ pub async fn add_lesson(
             .await?;
         }
         Ok(None) => {
-            if let Err(e) = save_message(pool, spam_token, text).await {
-                repl.send_text(internal_error(&e)).await?;
-            } else {
-                repl.send_text(&TEXT.lesson_saved).await?;
+            match save_message(pool, spam_token, text).await {
+                Ok(lesson_id) => {
+                    repl.send_text(&TEXT.lesson_saved).await?;
+                    post_to_moderators(pool, &repl.bot, lesson_id).await;
+                }
+                Err(e) => {
+                    repl.send_text(internal_error(&e)).await?;
+                }
             };
         }
     };
@@ -62,13 +68,15 @@ async fn is_flood(pool: &PgPool, spam_token: &str) -> sqlx::Result<Option<usize>
     Ok(Some(left.as_seconds_f32().ceil().abs() as usize))
 }
 
-async fn save_message(pool: &PgPool, spam_token: &str, text: &str) -> sqlx::Result<()> {
+/// Returns the new lesson id
+async fn save_message(pool: &PgPool, spam_token: &str, text: &str) -> sqlx::Result<i32> {
     query!(
-        "INSERT INTO lesson (text, spam_token) VALUES ($1, $2)",
+        "INSERT INTO lesson (text, spam_token, lang) VALUES ($1, $2, $3) RETURNING id",
         text,
-        spam_token
+        spam_token,
+        detect_lang(text),
     )
-    .execute(pool)
-    .await?;
-    Ok(())
+    .map(|row| row.id)
+    .fetch_one(pool)
+    .await
 }
@@ -0,0 +1,628 @@
+use crate::{
+    internal_error,
+    lesson::{Lesson, LessonStatus},
+    util::{escape_like, join_ids, preview},
+    Error, Replier, ReplyResult, CONF,
+};
+use sqlx::{query, PgPool};
+use std::{fmt::Write, str::FromStr};
+use teloxide::{
+    payloads::SendMessageSetters,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+const APPROVE_CMD: &str = "/approve";
+const REJECT_CMD: &str = "/reject";
+const REJECT_MATCHING_CMD: &str = "/reject-matching";
+const REJECT_MATCHING_APPLY_CMD: &str = "/reject-matching-apply";
+const BATCH_CMD: &str = "/batch";
+/// Lessons changed by one command, ranges like `20-25` add up quickly
+const MAX_BATCH_LESSONS: usize = 500;
+const MIN_PATTERN_LEN: usize = 3;
+const PREVIEW_LESSONS: usize = 10;
+/// New lessons on a page of the list view
+const PAGE_SIZE: usize = 10;
+/// The page selection is a bit mask of the ids in the page range
+const MAX_PAGE_RANGE: i32 = 64;
+const TEXT_PREVIEW_LEN: usize = 80;
+
+/// Moderates many lessons at once
+#[derive(Debug, PartialEq, Eq)]
+pub enum BatchCommand {
+    /// `/approve 12 13 20-25` or `/reject [reason] 12 13 20-25`
+    SetStatus {
+        status: LessonStatus,
+        reason: Option<String>,
+        lesson_ids: Vec<i32>,
+    },
+    /// `/reject-matching <pattern>` previews the new lessons containing the pattern,
+    /// `*` matches anything, `/reject-matching-apply <pattern>` rejects them
+    RejectMatching { pattern: String, apply: bool },
+    /// `/batch` lists the new lessons to select and moderate
+    List(BatchPage),
+}
+
+/// A page of new lessons in the list view
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchPage {
+    /// The page starts after this lesson
+    after: i32,
+    /// The last lesson on the page, it's found when the page is shown first
+    last: Option<i32>,
+    /// Bit `i` selects the lesson `after + 1 + i`, ids stay put when lessons are deleted
+    selected: u64,
+    /// Approve or reject the selected lessons
+    action: Option<LessonStatus>,
+    /// Index of the rejection reason in `CONF.rejection_reasons`, the reasons themselves
+    /// don't fit in the button data
+    reason: Option<usize>,
+}
+
+struct PageLesson {
+    id: i32,
+    text: String,
+    status: LessonStatus,
+}
+
+impl BatchCommand {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let (name, args) = cmd.split_once(char::is_whitespace).unwrap_or((cmd, ""));
+        let args = args.trim();
+        match name {
+            APPROVE_CMD | REJECT_CMD => {
+                let mut args = args.split(|c: char| c.is_whitespace() || c == ',');
+                let mut args = args.by_ref().filter(|s| !s.is_empty()).peekable();
+                let reason = match args.peek() {
+                    Some(reason) if name == REJECT_CMD && reason.parse::<i32>().is_err() => reason
+                        .contains(char::is_alphabetic)
+                        .then(|| reason.to_string()),
+                    _ => None,
+                };
+                if reason.is_some() {
+                    args.next();
+                }
+                let lesson_ids = parse_ids(args)?;
+                let status = if name == APPROVE_CMD {
+                    LessonStatus::Approved
+                } else {
+                    LessonStatus::Rejected
+                };
+                Some(Self::SetStatus {
+                    status,
+                    reason,
+                    lesson_ids,
+                })
+            }
+            REJECT_MATCHING_CMD | REJECT_MATCHING_APPLY_CMD => {
+                (args.chars().count() >= MIN_PATTERN_LEN).then(|| Self::RejectMatching {
+                    pattern: args.to_owned(),
+                    apply: name == REJECT_MATCHING_APPLY_CMD,
+                })
+            }
+            BATCH_CMD => BatchPage::from_args(args).map(Self::List),
+            _ => None,
+        }
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        match self {
+            Self::SetStatus {
+                status,
+                reason,
+                lesson_ids,
+            } => {
+                let res = Lesson::set_status(
+                    pool,
+                    lesson_ids,
+                    *status,
+                    reason.as_deref(),
+                    None,
+                    repl.sender_id(),
+                )
+                .await;
+                match res {
+                    Ok(changed) => {
+                        let skipped = lesson_ids
+                            .iter()
+                            .filter(|id| !changed.contains(id))
+                            .copied()
+                            .collect::<Vec<_>>();
+                        let mut text =
+                            format!("{} {} lesson(s)", status_done(*status), changed.len());
+                        if !skipped.is_empty() {
+                            write!(
+                                text,
+                                "\nSkipped {}: missing, claimed or can't become {}",
+                                join_ids(&skipped),
+                                status.as_ref(),
+                            )
+                            .ok();
+                        }
+                        repl.send_text(text).await?
+                    }
+                    Err(e @ Error::UnknownRejectionReason(..)) => {
+                        repl.send_text(e.to_string()).await?
+                    }
+                    Err(e) => repl.send_text(internal_error(&e)).await?,
+                };
+            }
+            Self::RejectMatching { pattern, apply } => {
+                let matching = match matching_lessons(pool, pattern).await {
+                    Ok(matching) => matching,
+                    Err(e) => {
+                        repl.send_text(internal_error(&e)).await?;
+                        return Ok(());
+                    }
+                };
+                if *apply {
+                    let ids = matching.iter().map(|l| l.id).collect::<Vec<_>>();
+                    let res = Lesson::set_status(
+                        pool,
+                        &ids,
+                        LessonStatus::Rejected,
+                        None,
+                        Some(LessonStatus::New),
+                        repl.sender_id(),
+                    )
+                    .await;
+                    match res {
+                        Ok(changed) => {
+                            repl.send_text(format!("👎 Rejected {} lesson(s)", changed.len()))
+                                .await?
+                        }
+                        Err(e) => repl.send_text(internal_error(&e)).await?,
+                    };
+                } else {
+                    let mut text =
+                        format!("{} new lesson(s) match \"{pattern}\":\n", matching.len());
+                    for lesson in matching.iter().take(PREVIEW_LESSONS) {
+                        write!(
+                            text,
+                            "\n{}. {}",
+                            lesson.id,
+                            preview(&lesson.text, TEXT_PREVIEW_LEN)
+                        )
+                        .ok();
+                    }
+                    if matching.len() > PREVIEW_LESSONS {
+                        text.push_str("\n…");
+                    }
+                    if !matching.is_empty() {
+                        write!(
+                            text,
+                            "\n\nTo reject them send:\n{REJECT_MATCHING_APPLY_CMD} {pattern}"
+                        )
+                        .ok();
+                    }
+                    repl.send_text(text).await?;
+                }
+            }
+            Self::List(page) => page.reply(pool, repl).await?,
+        }
+        Ok(())
+    }
+}
+
+impl BatchPage {
+    fn from_args(args: &str) -> Option<Self> {
+        let mut args = args.split_whitespace();
+        let (after, last, selected) = match (args.next(), args.next(), args.next()) {
+            (None, None, None) => return Some(Self::default()),
+            (Some(after), None, None) => {
+                return Some(Self {
+                    after: after.parse().ok()?,
+                    ..Self::default()
+                })
+            }
+            (Some(after), Some(last), Some(selected)) => (after, last, selected),
+            _ => return None,
+        };
+        let action = match args.next() {
+            None => None,
+            Some(action) => match LessonStatus::from_str(action).ok()? {
+                status @ (LessonStatus::Approved | LessonStatus::Rejected) => Some(status),
+                _ => return None,
+            },
+        };
+        let reason = match args.next() {
+            Some(reason) if action == Some(LessonStatus::Rejected) => Some(reason.parse().ok()?),
+            Some(_) => return None,
+            None => None,
+        };
+        if args.next().is_some() {
+            return None;
+        }
+        Some(Self {
+            after: after.parse().ok()?,
+            last: Some(last.parse().ok()?),
+            selected: u64::from_str_radix(selected, 16).ok()?,
+            action,
+            reason,
+        })
+    }
+
+    fn to_command(self) -> String {
+        let mut cmd = format!("{BATCH_CMD} {}", self.after);
+        if let Some(last) = self.last {
+            write!(cmd, " {last} {:x}", self.selected).ok();
+            if let Some(action) = self.action {
+                write!(cmd, " {}", action.as_ref()).ok();
+                if let Some(reason) = self.reason {
+                    write!(cmd, " {reason}").ok();
+                }
+            }
+        }
+        cmd
+    }
+
+    /// The selection bit of the lesson
+    fn bit(&self, lesson_id: i32) -> u64 {
+        1 << (lesson_id - self.after - 1)
+    }
+
+    fn is_selected(&self, lesson_id: i32) -> bool {
+        self.selected & self.bit(lesson_id) != 0
+    }
+
+    fn toggle(self, lesson_id: i32) -> Self {
+        Self {
+            selected: self.selected ^ self.bit(lesson_id),
+            action: None,
+            reason: None,
+            ..self
+        }
+    }
+
+    fn selected_ids(&self) -> Vec<i32> {
+        (0..MAX_PAGE_RANGE)
+            .filter(|i| self.selected & (1 << i) != 0)
+            .filter_map(|i| self.after.checked_add(1 + i))
+            .collect()
+    }
+
+    /// Applies the action to the selected new lessons and shows the page
+    async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let mut page = *self;
+        let mut notice = None;
+        // Buttons of the list view edit it, others open a new one
+        let edit = repl.callback && (page.after != 0 || page.last.is_some());
+        if let Some(action) = page.action {
+            // A reason missing from the config since the page was shown is reported unknown
+            let reason = page.reason.map(|i| {
+                CONF.rejection_reasons
+                    .get(i)
+                    .cloned()
+                    .unwrap_or_else(|| i.to_string())
+            });
+            let res = Lesson::set_status(
+                pool,
+                &page.selected_ids(),
+                action,
+                reason.as_deref(),
+                Some(LessonStatus::New),
+                repl.sender_id(),
+            )
+            .await;
+            notice = Some(match res {
+                Ok(changed) => {
+                    let mut notice = format!("{} {} lesson(s)", status_done(action), changed.len());
+                    if let Some(reason) = &reason {
+                        write!(notice, " as {reason}").ok();
+                    }
+                    notice
+                }
+                Err(e @ Error::UnknownRejectionReason(..)) => e.to_string(),
+                Err(e) => internal_error(&e).to(repl.lang),
+            });
+            page.selected = 0;
+            page.action = None;
+            page.reason = None;
+        }
+        match page.lessons(pool).await {
+            Ok(lessons) => page.render(repl, &lessons, notice, edit).await,
+            Err(e) => {
+                repl.send_text(internal_error(&e)).await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// The new lessons are selectable, others have been moderated since the page was shown
+    async fn render(
+        &self,
+        repl: &Replier,
+        lessons: &[PageLesson],
+        notice: Option<String>,
+        edit: bool,
+    ) -> ReplyResult {
+        let mut text = notice.map(|n| format!("{n}\n\n")).unwrap_or_default();
+        let new = lessons
+            .iter()
+            .filter(|l| l.status == LessonStatus::New)
+            .collect::<Vec<_>>();
+        if new.is_empty() {
+            text.push_str("No new lessons here");
+        } else {
+            text.push_str("New lessons, select them to moderate:\n");
+        }
+        let mut toggles = vec![];
+        let mut all = 0;
+        for lesson in &new {
+            let mark = if self.is_selected(lesson.id) {
+                "☑️"
+            } else {
+                "⬜"
+            };
+            write!(
+                text,
+                "\n{mark} {}. {}",
+                lesson.id,
+                preview(&lesson.text, TEXT_PREVIEW_LEN)
+            )
+            .ok();
+            toggles.push(InlineKeyboardButton::callback(
+                format!("{mark} {}", lesson.id),
+                self.toggle(lesson.id).to_command(),
+            ));
+            all |= self.bit(lesson.id);
+        }
+        let selected = new.iter().filter(|l| self.is_selected(l.id)).count();
+        let mut lines = toggles.chunks(5).map(<[_]>::to_vec).collect::<Vec<_>>();
+        if !new.is_empty() {
+            let select_all = Self {
+                selected: if self.selected & all == all { 0 } else { all },
+                ..*self
+            };
+            lines.push(vec![InlineKeyboardButton::callback(
+                "Select all",
+                select_all.to_command(),
+            )]);
+        }
+        if selected > 0 {
+            lines.push(
+                [
+                    (LessonStatus::Approved, "👍 Approve"),
+                    (LessonStatus::Rejected, "👎 Reject"),
+                ]
+                .map(|(action, label)| {
+                    InlineKeyboardButton::callback(
+                        format!("{label} {selected}"),
+                        Self {
+                            action: Some(action),
+                            ..*self
+                        }
+                        .to_command(),
+                    )
+                })
+                .to_vec(),
+            );
+            // Like the lesson buttons, rejecting with a reason is next to the plain one
+            let reasons = CONF
+                .rejection_reasons
+                .iter()
+                .enumerate()
+                .map(|(i, reason)| {
+                    InlineKeyboardButton::callback(
+                        format!("👎 {reason}"),
+                        Self {
+                            action: Some(LessonStatus::Rejected),
+                            reason: Some(i),
+                            ..*self
+                        }
+                        .to_command(),
+                    )
+                });
+            lines.extend(reasons.collect::<Vec<_>>().chunks(3).map(<[_]>::to_vec));
+        }
+        if let Some(last) = self.last {
+            lines.push(vec![InlineKeyboardButton::callback(
+                "Next ▶️",
+                Self {
+                    after: last,
+                    ..Self::default()
+                }
+                .to_command(),
+            )]);
+        }
+        let keyboard = InlineKeyboardMarkup::new(lines);
+        if edit {
+            repl.edit_or_send(text, keyboard).await
+        } else {
+            repl.send_text(text).reply_markup(keyboard).await?;
+            Ok(())
+        }
+    }
+
+    /// Lessons in the page id range. A new page starts at the first lesson after `after`
+    /// and ends at the `PAGE_SIZE`th new lesson or `MAX_PAGE_RANGE` ids later.
+    async fn lessons(&mut self, pool: &PgPool) -> Result<Vec<PageLesson>, Error> {
+        let lessons = query!(
+            r#"
+            SELECT id, text, status as "status: LessonStatus"
+            FROM lesson
+            WHERE id > $1 AND ($2::int IS NULL OR id <= $2) AND deleted_at IS NULL
+            ORDER BY id
+            LIMIT $3
+            "#,
+            self.after,
+            self.last,
+            i64::from(MAX_PAGE_RANGE),
+        )
+        .map(|r| PageLesson {
+            id: r.id,
+            text: r.text,
+            status: r.status,
+        })
+        .fetch_all(pool)
+        .await
+        .map_err(Error::BatchPage)?;
+        let fresh = self.last.is_none();
+        if let (true, Some(first)) = (fresh, lessons.first()) {
+            self.after = first.id - 1;
+        }
+        let after = self.after;
+        let mut new = 0;
+        let lessons = lessons
+            .into_iter()
+            .take_while(|l| i64::from(l.id) - i64::from(after) <= i64::from(MAX_PAGE_RANGE))
+            .take_while(|l| {
+                let more = !fresh || new < PAGE_SIZE;
+                new += usize::from(l.status == LessonStatus::New);
+                more
+            })
+            .collect::<Vec<_>>();
+        if fresh {
+            self.last = lessons.last().map(|l| l.id);
+        }
+        Ok(lessons)
+    }
+}
+
+/// New lessons containing the pattern, `*` matches anything
+async fn matching_lessons(pool: &PgPool, pattern: &str) -> Result<Vec<PageLesson>, Error> {
+    query!(
+        r#"
+        SELECT id, text, status as "status: LessonStatus"
+        FROM lesson
+        WHERE status = 'new' AND deleted_at IS NULL AND text ILIKE '%' || $1 || '%'
+        ORDER BY id
+        "#,
+        escape_like(pattern).replace('*', "%"),
+    )
+    .map(|r| PageLesson {
+        id: r.id,
+        text: r.text,
+        status: r.status,
+    })
+    .fetch_all(pool)
+    .await
+    .map_err(Error::MatchLessons)
+}
+
+/// Ids and ranges like `20-25`
+fn parse_ids<'a>(args: impl Iterator<Item = &'a str>) -> Option<Vec<i32>> {
+    let mut ids = vec![];
+    for arg in args {
+        let (first, last) = match arg.split_once('-') {
+            Some((first, last)) => (first.parse().ok()?, last.parse().ok()?),
+            None => {
+                let id = arg.parse().ok()?;
+                (id, id)
+            }
+        };
+        if first > last || (last - first) as usize >= MAX_BATCH_LESSONS {
+            return None;
+        }
+        for id in first..=last {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        if ids.len() > MAX_BATCH_LESSONS {
+            return None;
+        }
+    }
+    (!ids.is_empty()).then_some(ids)
+}
+
+fn status_done(status: LessonStatus) -> &'static str {
+    match status {
+        LessonStatus::Approved => "👍 Approved",
+        LessonStatus::Rejected => "👎 Rejected",
+        _ => "Updated",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_status_from_command() {
+        assert_eq!(
+            BatchCommand::from_command("/approve 12 13 20-22, 13"),
+            Some(BatchCommand::SetStatus {
+                status: LessonStatus::Approved,
+                reason: None,
+                lesson_ids: vec![12, 13, 20, 21, 22],
+            })
+        );
+        assert_eq!(
+            BatchCommand::from_command("/reject spam 5"),
+            Some(BatchCommand::SetStatus {
+                status: LessonStatus::Rejected,
+                reason: Some("spam".to_owned()),
+                lesson_ids: vec![5],
+            })
+        );
+        assert!(BatchCommand::from_command("/approve").is_none());
+        assert!(BatchCommand::from_command("/approve spam 5").is_none());
+        assert!(BatchCommand::from_command("/approve 5-1").is_none());
+        assert!(BatchCommand::from_command("/approve 1-100000").is_none());
+        assert!(BatchCommand::from_command("/reject spam").is_none());
+    }
+
+    #[test]
+    fn reject_matching_from_command() {
+        assert!(BatchCommand::from_command("/reject-matching ab").is_none());
+        assert_eq!(
+            BatchCommand::from_command("/reject-matching-apply buy *now"),
+            Some(BatchCommand::RejectMatching {
+                pattern: "buy *now".to_owned(),
+                apply: true,
+            })
+        );
+    }
+
+    #[test]
+    fn batch_page_command() {
+        assert_eq!(
+            BatchCommand::from_command("/batch"),
+            Some(BatchCommand::List(BatchPage::default()))
+        );
+        let page = BatchPage {
+            after: i32::MAX,
+            last: Some(i32::MAX),
+            selected: u64::MAX,
+            action: Some(LessonStatus::Rejected),
+            reason: Some(99),
+        };
+        let cmd = page.to_command();
+        assert!(cmd.len() <= 64, "{cmd}");
+        assert_eq!(
+            BatchCommand::from_command(&cmd),
+            Some(BatchCommand::List(page))
+        );
+        assert!(BatchCommand::from_command("/batch 1 2 3 best").is_none());
+        assert!(BatchCommand::from_command("/batch 1 2 3 approved 0").is_none());
+        assert!(BatchCommand::from_command("/batch 1 2 3 rejected spam").is_none());
+        assert!(BatchCommand::from_command("/batch 1 2").is_none());
+    }
+
+    #[test]
+    fn batch_selection_survives_deletions() {
+        let page = BatchPage {
+            after: 10,
+            last: Some(20),
+            ..BatchPage::default()
+        };
+        assert_eq!(page.toggle(11).selected, 1);
+        // Lesson 12 is deleted after the page is shown, the selection keeps to the ids
+        let page = page.toggle(13).toggle(20);
+        let cmd = BatchPage {
+            action: Some(LessonStatus::Approved),
+            ..page
+        }
+        .to_command();
+        match BatchCommand::from_command(&cmd) {
+            Some(BatchCommand::List(page)) => assert_eq!(page.selected_ids(), [13, 20]),
+            cmd => panic!("{cmd:?}"),
+        }
+        let end = BatchPage {
+            after: i32::MAX - 1,
+            selected: u64::MAX,
+            ..page
+        };
+        assert_eq!(end.selected_ids(), [i32::MAX]);
+    }
+}
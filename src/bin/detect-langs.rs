@@ -0,0 +1,17 @@
+//! Detects the language of lessons saved before the detection was introduced
+use sqlx::PgPool;
+use war_lessons_bot::{backfill_lesson_langs, eprint_error, Error, Result, CONF};
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    run().await.inspect_err(eprint_error)
+}
+
+async fn run() -> Result<()> {
+    let pool = PgPool::connect(&CONF.database_url)
+        .await
+        .map_err(Error::CreatePgPool)?;
+    let updated = backfill_lesson_langs(&pool).await?;
+    println!("{updated} lessons updated");
+    Ok(())
+}
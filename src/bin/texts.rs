@@ -0,0 +1,66 @@
+//! Translators workflow for `src/text.toml`:
+//!
+//! ```sh
+//! cargo run --bin texts -- export po be > be.po
+//! cargo run --bin texts -- export xliff be > be.xlf
+//! cargo run --bin texts -- import be.po
+//! ```
+use std::{env, fs, path::Path, process::exit, str::FromStr};
+use war_lessons_bot::{eprint_error, Error, Format, Languages, Result};
+
+const TEXT_TOML: &str = "src/text.toml";
+
+const USAGE: &str = "\
+Usage:
+  texts export <po|xliff> <lang>   print the texts to translate
+  texts import <file.po|file.xlf>  validate translations and write them into src/text.toml";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let res = match args.as_slice() {
+        ["export", format, lang] => match Format::from_str(format) {
+            Ok(format) => export(format, lang),
+            Err(_) => usage(),
+        },
+        ["import", path] => import(Path::new(path)),
+        _ => usage(),
+    };
+    if let Err(e) = res {
+        eprint_error(&e);
+        exit(1);
+    }
+}
+
+fn usage() -> Result<()> {
+    eprintln!("{USAGE}");
+    exit(2);
+}
+
+fn export(format: Format, lang: &str) -> Result<()> {
+    let langs = Languages::from_toml(&read(Path::new(TEXT_TOML))?)?;
+    print!("{}", format.export(&langs, lang));
+    Ok(())
+}
+
+fn import(path: &Path) -> Result<()> {
+    let format = match Format::from_path(path) {
+        Some(format) => format,
+        None => return usage(),
+    };
+    let catalog = format.parse(&read(path)?)?;
+    let text_toml = read(Path::new(TEXT_TOML))?;
+    let langs = Languages::from_toml(&text_toml)?;
+    let report = catalog.check(&langs)?;
+    print!("{report}");
+    if !report.translated.is_empty() {
+        fs::write(TEXT_TOML, report.apply(&text_toml)?)
+            .map_err(|e| Error::WriteFile(e, TEXT_TOML.into()))?;
+        println!("{TEXT_TOML} updated");
+    }
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|e| Error::ReadFile(e, path.display().to_string()))
+}
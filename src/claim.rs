@@ -0,0 +1,130 @@
+use crate::{internal_error, lesson::Lesson, Error, Replier, ReplyResult, CONF};
+use sqlx::{query, PgPool};
+
+const RELEASE_CMD: &str = "/release";
+
+/// Gives the lessons claimed by a moderator back to the queue
+#[derive(Debug, PartialEq, Eq)]
+pub struct ReleaseClaims;
+
+impl ReleaseClaims {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        (cmd == RELEASE_CMD).then_some(Self)
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let moderator = match repl.sender_id() {
+            Some(moderator) => moderator,
+            None => return Ok(()),
+        };
+        let res = query!(
+            "UPDATE lesson SET claimed_by = NULL, claimed_at = NULL WHERE claimed_by = $1",
+            moderator,
+        )
+        .execute(pool)
+        .await
+        .map_err(Error::ReleaseClaims);
+        match res {
+            Ok(done) => {
+                repl.send_text(format!("Released {} lesson(s)", done.rows_affected()))
+                    .await?
+            }
+            Err(e) => repl.send_text(internal_error(&e)).await?,
+        };
+        Ok(())
+    }
+}
+
+impl Lesson {
+    /// Claims a new lesson for the moderator, so others skip it, and releases their other
+    /// claims. Returns `false` if another moderator's claim is still valid.
+    pub(crate) async fn claim(
+        pool: &PgPool,
+        lesson_id: i32,
+        moderator: i64,
+    ) -> Result<bool, Error> {
+        query!(
+            r#"
+            WITH claimed AS (
+                UPDATE lesson
+                SET claimed_by = $2, claimed_at = now()
+                WHERE id = $1
+                  AND status = 'new'
+                  AND deleted_at IS NULL
+                  AND (claimed_by IS NULL
+                    OR claimed_by = $2
+                    OR claimed_at < now() - $3::float8 * interval '1 second')
+                RETURNING id
+            ), released AS (
+                UPDATE lesson
+                SET claimed_by = NULL, claimed_at = NULL
+                WHERE claimed_by = $2 AND id <> $1 AND EXISTS (SELECT 1 FROM claimed)
+            )
+            SELECT EXISTS (SELECT 1 FROM claimed) as "claimed!"
+            "#,
+            lesson_id,
+            moderator,
+            CONF.claim_timeout.as_secs_f64(),
+        )
+        .map(|r| r.claimed)
+        .fetch_one(pool)
+        .await
+        .map_err(|e| Error::ClaimLesson(e, lesson_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Lang, LessonReadOptions, LessonStatusRange, SortOrder};
+
+    #[test]
+    fn release_claims_from_command() {
+        assert_eq!(ReleaseClaims::from_command("/release"), Some(ReleaseClaims));
+        assert!(ReleaseClaims::from_command("/release 1").is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[ignore = "needs a scratch database and the .env config"]
+    async fn moderators_fetching_at_once_claim_different_lessons() {
+        const MODERATORS: i64 = 8;
+        let pool = PgPool::connect(&CONF.database_url).await.unwrap();
+        let mut lesson_ids = vec![];
+        for i in 0..MODERATORS * 2 {
+            let id = query!(
+                "INSERT INTO lesson (text) VALUES ($1) RETURNING id",
+                format!("Claim test lesson {i}"),
+            )
+            .fetch_one(&pool)
+            .await
+            .unwrap()
+            .id;
+            lesson_ids.push(id);
+        }
+        for _ in 0..10 {
+            let fetches = (1..=MODERATORS).map(|moderator| {
+                let pool = pool.clone();
+                tokio::spawn(async move {
+                    let view = LessonReadOptions::new(LessonStatusRange::New, None)
+                        .sort(SortOrder::Oldest);
+                    Lesson::get(&pool, &view, Lang::default(), None, Some(-moderator))
+                        .await
+                        .unwrap()
+                        .unwrap()
+                        .id
+                })
+            });
+            let mut fetched = vec![];
+            for fetch in fetches.collect::<Vec<_>>() {
+                fetched.push(fetch.await.unwrap());
+            }
+            fetched.sort_unstable();
+            fetched.dedup();
+            assert_eq!(fetched.len(), MODERATORS as usize, "{fetched:?}");
+        }
+        query!("DELETE FROM lesson WHERE id = ANY($1)", &lesson_ids)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+}
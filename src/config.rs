@@ -1,10 +1,20 @@
+use crate::retention::RetentionPolicy;
 use once_cell::sync::Lazy;
 use serde::Deserialize;
 use std::time::Duration;
 
+/// Shorter reader secrets are easy to brute-force over the small telegram ID space
+const MIN_READER_SECRET_LEN: usize = 32;
+/// Longer rejection reasons don't fit in the 64 bytes of telegram button data
+pub(crate) const MAX_REJECTION_REASON_LEN: usize = 12;
+
 pub static CONF: Lazy<Config> = Lazy::new(|| {
     dotenv::dotenv().expect("dotenv");
-    envy::from_env().expect("config")
+    let conf: Config = envy::from_env().expect("config");
+    if let Err(e) = conf.check() {
+        panic!("config: {e}");
+    }
+    conf
 });
 
 #[derive(Debug, Deserialize)]
@@ -20,4 +30,133 @@ pub struct Config {
     #[serde(default)]
     pub journal_logging: bool,
     pub moderators: Vec<i64>,
+    /// A stable secret of at least `MIN_READER_SECRET_LEN` characters to derive reader
+    /// pseudonyms from telegram IDs, never rotate it or readers lose their bookmarks
+    pub reader_secret: String,
+    /// How long to remember the lessons a reader has seen
+    #[serde(default = "default_read_state_lifetime", with = "humantime_serde")]
+    pub read_state_lifetime: Duration,
+    /// A group where new lessons are posted for moderation
+    #[serde(default)]
+    pub moderators_chat: Option<i64>,
+    /// How long a moderator viewing a new lesson keeps others from moderating it
+    #[serde(default = "default_claim_timeout", with = "humantime_serde")]
+    pub claim_timeout: Duration,
+    /// How many moderators must vote for a lesson to make it best, admins decide alone
+    #[serde(default = "default_best_quorum")]
+    pub best_quorum: usize,
+    /// Reasons a moderator picks from when rejecting a lesson, single words of up to
+    /// `MAX_REJECTION_REASON_LEN` bytes to fit in the button data
+    #[serde(default = "default_rejection_reasons")]
+    pub rejection_reasons: Vec<String>,
+    /// How long deleted lessons stay in the trash before they are purged for good
+    #[serde(default = "default_trash_retention", with = "humantime_serde")]
+    pub trash_retention: Duration,
+    /// Lessons that have kept a status that long since its change or their restore go to the
+    /// trash, like `rejected:90days`, none by default
+    #[serde(default)]
+    pub retention_policies: Vec<RetentionPolicy>,
+    /// Users allowed to edit the bot texts, delete and restore lessons
+    #[serde(default)]
+    pub admins: Vec<i64>,
+    /// How often to reload text overrides edited outside of the bot
+    #[serde(default = "default_text_refresh_interval", with = "humantime_serde")]
+    pub text_refresh_interval: Duration,
+    /// How long telegram may cache inline query results
+    #[serde(default = "default_inline_cache_time", with = "humantime_serde")]
+    pub inline_cache_time: Duration,
+}
+
+impl Config {
+    /// Refuses values the bot would only fail on at runtime
+    fn check(&self) -> Result<(), String> {
+        if self.reader_secret.trim().len() < MIN_READER_SECRET_LEN {
+            return Err(format!(
+                "READER_SECRET must be at least {MIN_READER_SECRET_LEN} characters, \
+                generate it once with `openssl rand -hex 32`"
+            ));
+        }
+        let bad_reason = self.rejection_reasons.iter().find(|reason| {
+            reason.len() > MAX_REJECTION_REASON_LEN
+                || reason.contains(char::is_whitespace)
+                || !reason.contains(char::is_alphabetic)
+        });
+        if let Some(reason) = bad_reason {
+            return Err(format!(
+                "REJECTION_REASONS has \"{reason}\", reasons must be single words of up to \
+                {MAX_REJECTION_REASON_LEN} bytes"
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn default_text_refresh_interval() -> Duration {
+    Duration::from_secs(60)
+}
+
+fn default_inline_cache_time() -> Duration {
+    Duration::from_secs(300)
+}
+
+fn default_read_state_lifetime() -> Duration {
+    Duration::from_secs(90 * 24 * 60 * 60)
+}
+
+fn default_trash_retention() -> Duration {
+    Duration::from_secs(30 * 24 * 60 * 60)
+}
+
+fn default_claim_timeout() -> Duration {
+    Duration::from_secs(10 * 60)
+}
+
+fn default_rejection_reasons() -> Vec<String> {
+    ["spam", "off-topic", "dangerous", "duplicate", "unclear"]
+        .map(String::from)
+        .to_vec()
+}
+
+fn default_best_quorum() -> usize {
+    2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(vars: &[(&str, &str)]) -> Result<Config, String> {
+        let required = [
+            ("DATABASE_URL", "postgres:///test"),
+            ("TELOXIDE_TOKEN", "token"),
+            ("SPAM_TOKEN_LIFETIME", "1h"),
+            ("RATE_LIMIT_MESSAGES", "5"),
+            ("RATE_LIMIT_DURATION", "5m"),
+            ("MODERATORS", "1"),
+            ("READER_SECRET", "0123456789abcdef0123456789abcdef"),
+        ];
+        let conf: Config = envy::from_iter(
+            required
+                .iter()
+                .filter(|(k, _)| !vars.iter().any(|(v, _)| v == k))
+                .chain(vars)
+                .map(|(k, v)| (k.to_string(), v.to_string())),
+        )
+        .map_err(|e| e.to_string())?;
+        conf.check().map(|_| conf)
+    }
+
+    #[test]
+    fn config_check() {
+        assert!(config(&[]).is_ok());
+        assert!(config(&[("READER_SECRET", "")]).is_err());
+        assert!(config(&[("READER_SECRET", "random-string-generated-once")]).is_err());
+        assert!(config(&[("REJECTION_REASONS", "spam,off-topic,profanity123")]).is_ok());
+        assert!(config(&[("REJECTION_REASONS", "spam,off topic")]).is_err());
+        assert!(config(&[("REJECTION_REASONS", "spam, off-topic")]).is_err());
+        assert!(config(&[("REJECTION_REASONS", "misinformation")]).is_err());
+        assert!(config(&[("REJECTION_REASONS", "spam,,unclear")]).is_err());
+        assert!(config(&[("REJECTION_REASONS", "42")]).is_err());
+        assert!(config(&[]).unwrap().retention_policies.is_empty());
+    }
 }
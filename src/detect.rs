@@ -0,0 +1,150 @@
+use crate::{Error, LANGS};
+use sqlx::{query, PgPool};
+use whatlang::{Detector, Info, Lang};
+
+/// A code for lessons we failed to detect the language of, they're shown in every language
+pub const UNDETERMINED_LANG: &str = "und";
+
+/// A confidence enough to choose among the bot languages
+const MIN_BOT_LANG_CONFIDENCE: f64 = 0.5;
+
+/// Detects the language of a text offline by its trigrams, returns an ISO 639-1 code.
+/// Similar languages are often confused on short texts, so when unsure we choose among the
+/// bot languages only.
+pub fn detect_lang(text: &str) -> &'static str {
+    let info = whatlang::detect(text)
+        .filter(Info::is_reliable)
+        .or_else(|| {
+            let bot_langs = LANGS.all().flat_map(crate::Lang::codes).collect::<Vec<_>>();
+            let allowlist = Lang::all()
+                .iter()
+                .copied()
+                .filter(|l| bot_langs.contains(&iso639_1(*l)))
+                .collect();
+            Detector::with_allowlist(allowlist)
+                .detect(text)
+                .filter(|info| info.confidence() >= MIN_BOT_LANG_CONFIDENCE)
+        });
+    info.map(|info| iso639_1(info.lang()))
+        .unwrap_or(UNDETERMINED_LANG)
+}
+
+/// Detects the language of lessons saved without one, returns the number of updated lessons
+pub async fn backfill_lesson_langs(pool: &PgPool) -> Result<usize, Error> {
+    let lessons = query!("SELECT id, text FROM lesson WHERE lang IS NULL")
+        .fetch_all(pool)
+        .await
+        .map_err(Error::BackfillLessonLangs)?;
+    for lesson in &lessons {
+        query!(
+            "UPDATE lesson SET lang = $1 WHERE id = $2",
+            detect_lang(&lesson.text),
+            lesson.id,
+        )
+        .execute(pool)
+        .await
+        .map_err(Error::BackfillLessonLangs)?;
+    }
+    Ok(lessons.len())
+}
+
+/// Converts an ISO 639-3 language to ISO 639-1 used by telegram
+fn iso639_1(lang: Lang) -> &'static str {
+    match lang {
+        Lang::Afr => "af",
+        Lang::Aka => "ak",
+        Lang::Amh => "am",
+        Lang::Ara => "ar",
+        Lang::Aze => "az",
+        Lang::Bel => "be",
+        Lang::Ben => "bn",
+        Lang::Bul => "bg",
+        Lang::Cat => "ca",
+        Lang::Ces => "cs",
+        Lang::Cmn => "zh",
+        Lang::Dan => "da",
+        Lang::Deu => "de",
+        Lang::Ell => "el",
+        Lang::Eng => "en",
+        Lang::Epo => "eo",
+        Lang::Est => "et",
+        Lang::Fin => "fi",
+        Lang::Fra => "fr",
+        Lang::Guj => "gu",
+        Lang::Heb => "he",
+        Lang::Hin => "hi",
+        Lang::Hrv => "hr",
+        Lang::Hun => "hu",
+        Lang::Hye => "hy",
+        Lang::Ind => "id",
+        Lang::Ita => "it",
+        Lang::Jav => "jv",
+        Lang::Jpn => "ja",
+        Lang::Kan => "kn",
+        Lang::Kat => "ka",
+        Lang::Khm => "km",
+        Lang::Kor => "ko",
+        Lang::Lat => "la",
+        Lang::Lav => "lv",
+        Lang::Lit => "lt",
+        Lang::Mal => "ml",
+        Lang::Mar => "mr",
+        Lang::Mkd => "mk",
+        Lang::Mya => "my",
+        Lang::Nep => "ne",
+        Lang::Nld => "nl",
+        Lang::Nob => "nb",
+        Lang::Ori => "or",
+        Lang::Pan => "pa",
+        Lang::Pes => "fa",
+        Lang::Pol => "pl",
+        Lang::Por => "pt",
+        Lang::Ron => "ro",
+        Lang::Rus => "ru",
+        Lang::Sin => "si",
+        Lang::Slk => "sk",
+        Lang::Slv => "sl",
+        Lang::Sna => "sn",
+        Lang::Spa => "es",
+        Lang::Srp => "sr",
+        Lang::Swe => "sv",
+        Lang::Tam => "ta",
+        Lang::Tel => "te",
+        Lang::Tgl => "tl",
+        Lang::Tha => "th",
+        Lang::Tuk => "tk",
+        Lang::Tur => "tr",
+        Lang::Ukr => "uk",
+        Lang::Urd => "ur",
+        Lang::Uzb => "uz",
+        Lang::Vie => "vi",
+        Lang::Yid => "yi",
+        Lang::Zul => "zu",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect() {
+        assert_eq!(
+            detect_lang("Write to your local representatives and explain why the war must stop"),
+            "en"
+        );
+        assert_eq!(
+            detect_lang("Пишите своим депутатам и объясняйте, почему войну нужно остановить"),
+            "ru"
+        );
+        assert_eq!(
+            detect_lang("Пишіть своїм депутатам і пояснюйте, чому війну треба зупинити"),
+            "uk"
+        );
+        assert_eq!(
+            detect_lang("Донатьте фондам которые помогают беженцам, это реально важно"),
+            "ru"
+        );
+        assert_eq!(detect_lang("👍"), UNDETERMINED_LANG);
+    }
+}
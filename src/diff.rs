@@ -0,0 +1,55 @@
+use similar::{ChangeTag, TextDiff};
+
+/// Whitespace and words around each change
+const CONTEXT: usize = 6;
+
+/// Shows word changes like `git diff --word-diff`: `[-removed-]{+added+}`, unchanged
+/// parts far from changes are skipped with `…`
+pub(crate) fn word_diff(old: &str, new: &str) -> String {
+    let diff = TextDiff::from_words(old, new);
+    let mut groups = vec![];
+    for ops in diff.grouped_ops(CONTEXT) {
+        let mut group = String::new();
+        let mut tag = ChangeTag::Equal;
+        for change in ops.iter().flat_map(|op| diff.iter_changes(op)) {
+            if change.tag() != tag {
+                close(&mut group, tag);
+                tag = change.tag();
+                group.push_str(match tag {
+                    ChangeTag::Delete => "[-",
+                    ChangeTag::Insert => "{+",
+                    ChangeTag::Equal => "",
+                });
+            }
+            group.push_str(change.value());
+        }
+        close(&mut group, tag);
+        groups.push(group);
+    }
+    groups.join(" … ")
+}
+
+fn close(group: &mut String, tag: ChangeTag) {
+    group.push_str(match tag {
+        ChangeTag::Delete => "-]",
+        ChangeTag::Insert => "+}",
+        ChangeTag::Equal => "",
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn word_diff_marks_changes() {
+        assert_eq!(word_diff("same", "same"), "");
+        assert_eq!(
+            word_diff("call Ivan at 555 today", "call them today"),
+            "call [-Ivan at 555-]{+them+} today"
+        );
+        let old = "one two three four five six seven eight nine ten";
+        let new = "one two three four five six seven eight nine TEN";
+        assert_eq!(word_diff(old, new), "seven eight nine [-ten-]{+TEN+}");
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{
+    detect_lang, internal_error, lesson::Lesson, message_text, Error, Replier, ReplyResult, TEXT,
+};
+use sqlx::{query, PgPool};
+use teloxide::{
+    payloads::SendMessageSetters,
+    types::{ForceReply, Message},
+};
+
+const EDIT_CMD: &str = "/edit";
+/// Starts the message moderators reply to with the new text, the lesson id follows
+const EDIT_PROMPT: &str = "✏️ Reply with the new text of lesson ";
+
+/// Replaces the lesson text with a moderator's edit, the submitted text is kept. The edit
+/// button asks for the text in reply, `/edit <id> <text>` works too.
+#[derive(Debug, PartialEq, Eq)]
+pub struct EditLesson {
+    pub(crate) lesson_id: i32,
+    /// Without a text we reply with the instructions
+    pub(crate) text: Option<String>,
+}
+
+impl EditLesson {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let (name, args) = cmd.split_once(char::is_whitespace)?;
+        if name != EDIT_CMD {
+            return None;
+        }
+        let args = args.trim_start();
+        let (lesson_id, text) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let text = text.trim();
+        Some(Self {
+            lesson_id: lesson_id.parse().ok()?,
+            text: (!text.is_empty()).then(|| text.to_owned()),
+        })
+    }
+
+    /// A reply to the bot's edit prompt carries the new text
+    pub fn from_reply(message: &Message) -> Option<Self> {
+        let prompt = message.reply_to_message()?;
+        if !prompt.from().is_some_and(|u| u.is_bot) {
+            return None;
+        }
+        let lesson_id = prompt_lesson_id(message_text(prompt)?)?;
+        let text = message_text(message)?.trim();
+        (!text.is_empty()).then(|| Self {
+            lesson_id,
+            text: Some(text.to_owned()),
+        })
+    }
+
+    pub(crate) fn to_command(&self) -> String {
+        format!("{EDIT_CMD} {}", self.lesson_id)
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let text = match &self.text {
+            Some(text) => text,
+            None => {
+                match Lesson::find(pool, self.lesson_id, repl.lang, None).await {
+                    Ok(Some(lesson)) => {
+                        // Only the moderator who tapped the button gets the reply field in
+                        // the moderators chat
+                        let who = repl
+                            .from
+                            .as_ref()
+                            .and_then(|u| u.mention())
+                            .filter(|_| repl.in_moderators_chat());
+                        repl.send_text(format!(
+                            "{}\n\nThe current text:\n\n{}",
+                            prompt(self.lesson_id, who.as_deref()),
+                            lesson.text,
+                        ))
+                        .reply_markup(ForceReply::new().selective(true))
+                        .await?
+                    }
+                    Ok(None) => repl.send_text(&TEXT.lesson_not_found).await?,
+                    Err(e) => repl.send_text(internal_error(&e)).await?,
+                };
+                return Ok(());
+            }
+        };
+        let moderator = repl.sender_id();
+        match Lesson::edit(pool, self.lesson_id, text, moderator).await {
+            Ok(true) => {
+                repl.send_text(format!("✅ Lesson {} edited", self.lesson_id))
+                    .await?
+            }
+            Ok(false) => repl.send_text(&TEXT.lesson_not_found).await?,
+            Err(e) => repl.send_text(internal_error(&e)).await?,
+        };
+        Ok(())
+    }
+}
+
+fn prompt(lesson_id: i32, who: Option<&str>) -> String {
+    match who {
+        Some(who) => format!("{EDIT_PROMPT}{lesson_id}, {who}"),
+        None => format!("{EDIT_PROMPT}{lesson_id}"),
+    }
+}
+
+fn prompt_lesson_id(prompt: &str) -> Option<i32> {
+    let rest = prompt.strip_prefix(EDIT_PROMPT)?;
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
+
+impl Lesson {
+    /// Replaces the text, the first edit keeps the submitted text in `original_text`
+    async fn edit(
+        pool: &PgPool,
+        lesson_id: i32,
+        text: &str,
+        moderator: Option<i64>,
+    ) -> Result<bool, Error> {
+        query!(
+            r#"
+            UPDATE lesson
+            SET original_text = coalesce(original_text, text),
+                text = $2,
+                lang = $3,
+                edited_by = $4,
+                edited_at = now()
+            WHERE id = $1 AND deleted_at IS NULL
+            "#,
+            lesson_id,
+            text,
+            detect_lang(text),
+            moderator,
+        )
+        .execute(pool)
+        .await
+        .map(|done| done.rows_affected() > 0)
+        .map_err(|e| Error::EditLesson(e, lesson_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_lesson_from_command() {
+        assert!(EditLesson::from_command("/edit").is_none());
+        assert!(EditLesson::from_command("/edit x").is_none());
+        assert_eq!(
+            EditLesson::from_command("/edit 3").unwrap(),
+            EditLesson {
+                lesson_id: 3,
+                text: None
+            }
+        );
+        assert_eq!(
+            EditLesson::from_command("/edit 3 Line one\nline two ").unwrap(),
+            EditLesson {
+                lesson_id: 3,
+                text: Some("Line one\nline two".to_owned())
+            }
+        );
+    }
+
+    #[test]
+    fn edit_prompt_lesson_id() {
+        assert_eq!(prompt_lesson_id(&prompt(12, None)), Some(12));
+        assert_eq!(
+            prompt_lesson_id(&format!("{}\n\nThe current text", prompt(7, Some("@mod")))),
+            Some(7)
+        );
+        assert_eq!(
+            prompt_lesson_id("Reply with the new text of lesson 7"),
+            None
+        );
+        assert_eq!(prompt_lesson_id(EDIT_PROMPT), None);
+    }
+}
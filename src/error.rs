@@ -1,22 +1,116 @@
-use crate::{lesson::LessonStatus, Translations, TEXT};
+use crate::{lesson::LessonStatus, Lang, LessonStatusRange, Translations, TEXT};
 use std::fmt::Write;
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum Error {
     /// Lesson::get({1:?}, {2:?})
-    ReadNextLesson(#[source] sqlx::Error, LessonStatus, Option<i32>),
+    ReadNextLesson(#[source] sqlx::Error, LessonStatusRange, Option<i32>),
     /// LessonModeration::get({1})
     GetLessonModeration(#[source] sqlx::Error, i32),
-    /// SetLessonStatus::set({1}, {2:?})
-    SetLessonStatus(#[source] sqlx::Error, i32, LessonStatus),
+    /// Lesson::position({1})
+    LessonPosition(#[source] sqlx::Error, i32),
+    /// Lesson::find({1})
+    FindLesson(#[source] sqlx::Error, i32),
+    /// Lesson::claim({1})
+    ClaimLesson(#[source] sqlx::Error, i32),
+    /// Add a note to lesson {1}
+    AddNote(#[source] sqlx::Error, i32),
+    /// List notes on lesson {1}
+    ListNotes(#[source] sqlx::Error, i32),
+    /// Rejection report
+    RejectionReport(#[source] sqlx::Error),
+    /// Vote for lesson {1} to be best
+    VoteBest(#[source] sqlx::Error, i32),
+    /// Edit lesson {1}
+    EditLesson(#[source] sqlx::Error, i32),
+    /// Split or merge lessons {1:?}
+    SupersedeLessons(#[source] sqlx::Error, Vec<i32>),
+    /// Batch moderation page
+    BatchPage(#[source] sqlx::Error),
+    /// Find lessons matching a pattern
+    MatchLessons(#[source] sqlx::Error),
+    /// Delete lesson {1}
+    DeleteLesson(#[source] sqlx::Error, i32),
+    /// Restore lesson {1}
+    RestoreLesson(#[source] sqlx::Error, i32),
+    /// List the trash
+    ListTrash(#[source] sqlx::Error),
+    /// Deletion history of lesson {1}
+    DeletionHistory(#[source] sqlx::Error, i32),
+    /// Purge the trash
+    PurgeTrash(#[source] sqlx::Error),
+    /// Retention report
+    RetentionReport(#[source] sqlx::Error),
+    /// Apply retention policies
+    ApplyRetention(#[source] sqlx::Error),
+    /// Release claims
+    ReleaseClaims(#[source] sqlx::Error),
+    /// Lesson::save({1})
+    SaveLesson(#[source] sqlx::Error, i32),
+    /// mark_read({1})
+    MarkLessonRead(#[source] sqlx::Error, i32),
+    /// Reset read state
+    ResetReadState(#[source] sqlx::Error),
+    /// Purge read state
+    PurgeReadState(#[source] sqlx::Error),
+    /// Subscribe
+    Subscribe(#[source] sqlx::Error),
+    /// Unsubscribe
+    Unsubscribe(#[source] sqlx::Error),
+    /// List subscriptions
+    ListSubscriptions(#[source] sqlx::Error),
+    /// Notify subscribers
+    NotifySubscribers(#[source] sqlx::Error),
+    /// Lesson::translate({1}, {2:?})
+    TranslateLesson(#[source] sqlx::Error, i32, Lang),
+    /// A lesson can't change from {0:?} to {1:?}
+    IllegalStatusChange(LessonStatus, LessonStatus),
+    /// Unknown rejection reason {0}, use one of: {1}
+    UnknownRejectionReason(String, String),
+    /// Lesson::set_status({1:?}, {2:?})
+    SetLessonStatus(#[source] sqlx::Error, Vec<i32>, LessonStatus),
+    /// Backfill lesson languages
+    BackfillLessonLangs(#[source] sqlx::Error),
+    /// Search lessons
+    SearchLessons(#[source] sqlx::Error),
     /// LessonModeration::next
     NextLessonModeration(#[source] sqlx::Error),
     /// Systemd logging init
     InitSystemdLogging(#[source] log::SetLoggerError),
+    /// Get the bot info
+    GetMe(#[source] teloxide::RequestError),
     /// Create pg pool
     CreatePgPool(#[source] sqlx::Error),
     /// Migrate
     Migrate(#[source] sqlx::migrate::MigrateError),
+    /// Load text overrides
+    LoadTextOverrides(#[source] sqlx::Error),
+    /// Set text override {1} {2:?}
+    SetTextOverride(#[source] sqlx::Error, String, Lang),
+    /// Reset text override {1} {2:?}
+    ResetTextOverride(#[source] sqlx::Error, String, Lang),
+    /// Read {1}
+    ReadFile(#[source] std::io::Error, String),
+    /// Write {1}
+    WriteFile(#[source] std::io::Error, String),
+    /// Edit text.toml
+    EditText(#[source] toml_edit::TomlError),
+    /// PO syntax error at line {0}
+    ParsePo(usize),
+    /// Parse XLIFF
+    ParseXliff(#[source] quick_xml::Error),
+    /// The file doesn't specify a language
+    NoCatalogLanguage,
+    /// Unknown language `{0}`, add it to the `[languages]` table of text.toml first
+    UnknownCatalogLanguage(String),
+    /// Parse text.toml
+    ParseText(#[source] toml::de::Error),
+    /// Text `{0}` has no default translation
+    NoDefaultTranslation(String),
+    /// Language `{0}` falls back to unknown `{1}`
+    UnknownFallback(String, String),
+    /// Language `{0}` has a fallback cycle
+    FallbackCycle(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -0,0 +1,131 @@
+/// Tags supported by telegram in `ParseMode::Html`
+const TAGS: &[&str] = &[
+    "a",
+    "b",
+    "blockquote",
+    "code",
+    "del",
+    "em",
+    "i",
+    "ins",
+    "pre",
+    "s",
+    "span",
+    "strike",
+    "strong",
+    "tg-emoji",
+    "tg-spoiler",
+    "u",
+];
+
+/// Named entities supported by telegram
+const ENTITIES: &[&str] = &["amp", "gt", "lt", "quot"];
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+pub enum HtmlError {
+    /// Unsupported tag <{0}>
+    UnsupportedTag(String),
+    /// Unexpected closing tag </{0}>
+    UnexpectedClose(String),
+    /// Unclosed tag <{0}>
+    Unclosed(String),
+    /// Unterminated tag at position {0}, escape `<` as `&lt;`
+    Unterminated(usize),
+    /// Bad entity at position {0}, escape `&` as `&amp;`
+    BadEntity(usize),
+}
+
+/// Validates a telegram html message and returns its tag names in order, closing ones are
+/// prefixed with `/`
+pub fn tags(html: &str) -> Result<Vec<String>, HtmlError> {
+    let mut tags = vec![];
+    let mut open = vec![];
+    let mut pos = 0;
+    while let Some(offset) = html[pos..].find(['<', '&']) {
+        let start = pos + offset;
+        if html[start..].starts_with('&') {
+            let end = html[start..]
+                .find(';')
+                .map(|end| start + end)
+                .ok_or(HtmlError::BadEntity(start))?;
+            if !is_entity(&html[start + 1..end]) {
+                return Err(HtmlError::BadEntity(start));
+            }
+            pos = end + 1;
+            continue;
+        }
+
+        let end = html[start..]
+            .find('>')
+            .map(|end| start + end)
+            .ok_or(HtmlError::Unterminated(start))?;
+        let tag = &html[start + 1..end];
+        if let Some(name) = tag.strip_prefix('/') {
+            let name = name.trim().to_lowercase();
+            if open.pop().as_ref() != Some(&name) {
+                return Err(HtmlError::UnexpectedClose(name));
+            }
+            tags.push(format!("/{name}"));
+        } else {
+            let name = tag
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_lowercase();
+            if !TAGS.contains(&name.as_str()) {
+                return Err(HtmlError::UnsupportedTag(name));
+            }
+            open.push(name.clone());
+            tags.push(name);
+        }
+        pos = end + 1;
+    }
+    if let Some(name) = open.pop() {
+        return Err(HtmlError::Unclosed(name));
+    }
+    Ok(tags)
+}
+
+fn is_entity(name: &str) -> bool {
+    if let Some(hex) = name.strip_prefix("#x") {
+        !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit())
+    } else if let Some(dec) = name.strip_prefix('#') {
+        !dec.is_empty() && dec.chars().all(|c| c.is_ascii_digit())
+    } else {
+        ENTITIES.contains(&name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid() {
+        assert_eq!(tags("plain text"), Ok(vec![]));
+        assert_eq!(
+            tags("<b>bold</b> &amp; <a href=\"https://t.me\">link</a> &#128512;"),
+            Ok(vec!["b".into(), "/b".into(), "a".into(), "/a".into()])
+        );
+        assert_eq!(
+            tags("<b><i>nested</i></b>"),
+            Ok(vec!["b".into(), "i".into(), "/i".into(), "/b".into()])
+        );
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(
+            tags("<div>x</div>"),
+            Err(HtmlError::UnsupportedTag("div".into()))
+        );
+        assert_eq!(
+            tags("<b><i>x</b></i>"),
+            Err(HtmlError::UnexpectedClose("b".into()))
+        );
+        assert_eq!(tags("<b>x"), Err(HtmlError::Unclosed("b".into())));
+        assert_eq!(tags("1 < 2"), Err(HtmlError::Unterminated(2)));
+        assert_eq!(tags("A & B"), Err(HtmlError::BadEntity(2)));
+        assert_eq!(tags("&nbsp;"), Err(HtmlError::BadEntity(0)));
+    }
+}
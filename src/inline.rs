@@ -0,0 +1,101 @@
+use crate::{
+    lesson::lesson_link,
+    log_error,
+    util::{escape_like, truncate},
+    Error, Lang, ReplyResult, CONF, TEXT,
+};
+use sqlx::{query_as, PgPool};
+use teloxide::{
+    adaptors::AutoSend,
+    payloads::AnswerInlineQuerySetters,
+    requests::Requester,
+    types::{
+        InlineKeyboardButton, InlineKeyboardMarkup, InlineQuery, InlineQueryResult,
+        InlineQueryResultArticle, InputMessageContent, InputMessageContentText,
+    },
+    Bot,
+};
+
+/// Results per page, telegram allows up to 50
+const PAGE_SIZE: i64 = 20;
+const TITLE_LEN: usize = 64;
+const DESCRIPTION_LEN: usize = 128;
+
+struct FoundLesson {
+    id: i32,
+    text: String,
+}
+
+/// Searches approved and best lessons by text to share them in any chat.
+/// Queries are private: neither the query nor the user get logged.
+pub async fn answer_inline_query(
+    bot: &AutoSend<Bot>,
+    pool: &PgPool,
+    q: &InlineQuery,
+) -> ReplyResult {
+    let offset = q.offset.parse().unwrap_or(0);
+    let lessons = match search(pool, &q.query, offset).await {
+        Ok(lessons) => lessons,
+        Err(e) => {
+            log_error(&e);
+            vec![]
+        }
+    };
+    let next_offset = if lessons.len() as i64 > PAGE_SIZE {
+        (offset + PAGE_SIZE).to_string()
+    } else {
+        String::new()
+    };
+    let lang = Lang::from(&q.from);
+    let results = lessons
+        .iter()
+        .take(PAGE_SIZE as usize)
+        .map(|lesson| article(lesson, lang));
+    bot.answer_inline_query(&q.id, results)
+        .cache_time(CONF.inline_cache_time.as_secs() as u32)
+        .is_personal(false)
+        .next_offset(next_offset)
+        .await?;
+    Ok(())
+}
+
+/// Returns a page of lessons containing the `text`, best ones first, plus one more lesson
+/// to know if there is a next page
+async fn search(pool: &PgPool, text: &str, offset: i64) -> Result<Vec<FoundLesson>, Error> {
+    query_as!(
+        FoundLesson,
+        r#"
+        SELECT id, text
+        FROM lesson
+        WHERE status IN ('approved', 'best') AND deleted_at IS NULL
+          AND ($1 = '' OR text ILIKE '%' || $1 || '%')
+        ORDER BY status DESC, id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        escape_like(text.trim()),
+        PAGE_SIZE + 1,
+        offset.max(0),
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(Error::SearchLessons)
+}
+
+fn article(lesson: &FoundLesson, lang: Lang) -> InlineQueryResult {
+    let (title, description) = lesson
+        .text
+        .trim()
+        .split_once('\n')
+        .unwrap_or((lesson.text.trim(), ""));
+    let content = InputMessageContent::Text(InputMessageContentText::new(&lesson.text));
+    let mut article =
+        InlineQueryResultArticle::new(lesson.id.to_string(), truncate(title, TITLE_LEN), content)
+            .description(truncate(description.trim(), DESCRIPTION_LEN));
+    if let Some(url) = lesson_link(lesson.id) {
+        article = article.reply_markup(InlineKeyboardMarkup::new([[InlineKeyboardButton::url(
+            TEXT.more_lessons.to(lang),
+            url,
+        )]]));
+    }
+    InlineQueryResult::Article(article)
+}
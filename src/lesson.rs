@@ -1,20 +1,94 @@
-use crate::{internal_error, start_keyboard, Error, Lang, Replier, ReplyResult, TEXT};
-use sqlx::{query_as, PgPool};
-use std::{convert::AsRef, str::FromStr};
+use crate::{
+    diff::word_diff, internal_error, log_error, read_state::mark_read, start_keyboard,
+    util::join_ids, Error, Lang, Replier, ReplyResult, CONF, LANGS, TEXT, UNDETERMINED_LANG,
+};
+use once_cell::sync::OnceCell;
+use sqlx::{query, query_as, PgPool};
+use std::{borrow::Cow, convert::AsRef, fmt::Write, str::FromStr};
 use strum_macros::{AsRefStr, EnumString};
 use teloxide::{
     payloads::{EditMessageTextSetters, SendMessageSetters},
     types::{InlineKeyboardButton, InlineKeyboardMarkup},
 };
 use time::OffsetDateTime;
+use url::Url;
 
+const START_CMD: &str = "/start";
+const LESSON_CMD: &str = "/lesson";
 const VIEW_CMD: &str = "/view";
-const SET_STATUS_CMD: &str = "/set-lesson-status";
+const RANDOM_CMD: &str = "/random";
+const DAILY_CMD: &str = "/daily";
+const SAVED_CMD: &str = "/saved";
+const SAVE_CMD: &str = "/save";
+const UNSAVE_CMD: &str = "/unsave";
+const TRANSLATE_CMD: &str = "/translate";
+const TOGGLE_TRANSLATION_CMD: &str = "/lesson-text";
+/// A view flag to show lessons in the reader's language only, e.g. `/view best+own`
+const OWN_LANG_FLAG: &str = "own";
+/// A view flag to navigate by editing one message, e.g. `/view best+carousel`
+const CAROUSEL_FLAG: &str = "carousel";
+/// A view flag to read the reader's bookmarks, e.g. `/view approved+saved`
+const SAVED_FLAG: &str = "saved";
+/// A view flag to skip lessons the reader has seen, e.g. `/view approved+unread`
+const UNREAD_FLAG: &str = "unread";
+/// A `/start` payload of lesson deep links, e.g. `t.me/<bot>?start=lesson_12`
+const DEEP_LINK_PREFIX: &str = "lesson_";
+/// Callback data of buttons that only show information
+pub const NOOP_CALLBACK: &str = "noop";
+/// How much more likely a best lesson is to be shown in the random and daily modes
+const BEST_WEIGHT: f64 = 3.0;
+/// Lessons shown before the previous one the random mode remembers to avoid repeats
+const RECENT_LESSONS: usize = 2;
+
+/// The bot username for deep links, known after `getMe`
+static BOT_USERNAME: OnceCell<String> = OnceCell::new();
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct LessonReadOptions {
     status_range: LessonStatusRange,
     prev_lesson: Option<i32>,
+    /// Read backward in the sort order from the `prev_lesson`, encoded as `<id`
+    backward: bool,
+    /// Show only lessons in the reader's language and undetermined ones
+    own_lang: bool,
+    mode: ReadMode,
+    /// Edit the lesson message instead of sending a new one
+    carousel: bool,
+    sort: SortOrder,
+    /// Show only lessons bookmarked by the reader
+    saved: bool,
+    /// Skip lessons the reader has seen recently
+    unread: bool,
+    /// Lessons shown before the `prev_lesson` in the random mode, newest first, encoded
+    /// after it like `/view aR 12 7 3`. They're shown again only when nothing else is left.
+    recent: [Option<i32>; RECENT_LESSONS],
+}
+
+/// Order of sequential reading, encoded as a view flag e.g. `/view new+oldest`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Newest,
+    Oldest,
+    /// The status rank is the score: best lessons first, then approved ones and so on in the
+    /// `lesson_status` order, newest first within a status. Pending best votes don't count.
+    Score,
+}
+
+/// How to choose the next lesson, encoded as a view flag e.g. `/view approved+random`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "snake_case")]
+pub enum ReadMode {
+    /// Newest first
+    #[default]
+    Sequential,
+    /// Random lessons weighted toward the best ones, the previous one is skipped and a few
+    /// recent ones come last
+    Random,
+    /// The lesson of the day, the same for everyone during a day, weighted toward the best
+    /// ones like random lessons
+    Daily,
 }
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, AsRefStr)]
@@ -29,29 +103,87 @@ pub enum LessonStatusRange {
 }
 
 pub struct Lesson {
-    id: i32,
-    text: String,
-    status: LessonStatus,
-    created_at: OffsetDateTime,
+    pub(crate) id: i32,
+    pub(crate) text: String,
+    pub(crate) status: LessonStatus,
+    pub(crate) created_at: OffsetDateTime,
+    /// ISO 639-1 code of the detected language
+    pub(crate) lang: Option<String>,
+    /// A moderator's translation to the reader's language
+    pub(crate) translation: Option<String>,
+    /// Whether the reader bookmarked the lesson, unknown outside of private chats
+    pub(crate) saved: Option<bool>,
+    /// Why the lesson is rejected
+    pub(crate) reason: Option<String>,
+    /// Lessons this one was split or merged into
+    pub(crate) superseded_by: Vec<i32>,
+    /// The submitted text if a moderator has edited it
+    pub(crate) original_text: Option<String>,
+    /// Moderators' votes to make the lesson best
+    pub(crate) best_votes: i64,
+    /// The latest three moderator notes, newest first, `/notes` lists them all
+    pub(crate) notes: Vec<String>,
 }
 
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, EnumString, AsRefStr, sqlx::Type)]
-#[sqlx(type_name = "lesson_status", rename_all = "lowercase")]
+#[sqlx(type_name = "lesson_status", rename_all = "snake_case")]
 #[strum(serialize_all = "snake_case")]
 pub enum LessonStatus {
+    /// Split or merged into other lessons, see `lesson_lineage`
+    Superseded,
+    /// The author asked to remove the lesson
+    Withdrawn,
+    /// Was public, hidden now
+    Archived,
+    /// Repeats another lesson
+    Duplicate,
     Rejected,
+    /// Waits for a moderator to fix the text before approval
+    NeedsEdit,
     New,
     #[default]
     Approved,
     Best,
 }
 
+impl sqlx::postgres::PgHasArrayType for LessonStatus {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_lesson_status")
+    }
+}
+
+/// Shows a lesson by its id from `/lesson <id>` or a deep link `/start lesson_<id>`
 #[derive(Debug, PartialEq, Eq)]
-pub struct SetLessonStatus {
-    /// Kind of lessons we're moderating
-    status_range: LessonStatusRange,
+pub struct ShowLesson {
+    lesson_id: i32,
+}
+
+/// Attaches a moderator's translation to a lesson
+#[derive(Debug, PartialEq, Eq)]
+pub struct TranslateLesson {
+    pub(crate) lesson_id: i32,
+    /// Without a translation we reply with the instructions
+    pub(crate) translation: Option<(Lang, String)>,
+}
+
+/// Adds a lesson to the reader's bookmarks or removes it
+#[derive(Debug, PartialEq, Eq)]
+pub struct SaveLesson {
+    /// Reading options to keep in the keyboard
+    view: LessonReadOptions,
     lesson_id: i32,
-    status: LessonStatus,
+    save: bool,
+    /// The message shows the original rather than the translation
+    original: bool,
+}
+
+/// Switches a lesson message between the translation and the original
+#[derive(Debug, PartialEq, Eq)]
+pub struct ToggleTranslation {
+    /// Reading options to keep in the keyboard
+    view: LessonReadOptions,
+    lesson_id: i32,
+    original: bool,
 }
 
 impl LessonReadOptions {
@@ -59,27 +191,66 @@ impl LessonReadOptions {
         Self {
             status_range,
             prev_lesson,
+            ..Default::default()
         }
     }
 
+    pub fn own_lang(mut self, own_lang: bool) -> Self {
+        self.own_lang = own_lang;
+        self
+    }
+
+    pub fn mode(mut self, mode: ReadMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn sort(mut self, sort: SortOrder) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn carousel(mut self, carousel: bool) -> Self {
+        self.carousel = carousel;
+        self
+    }
+
+    pub fn saved(mut self, saved: bool) -> Self {
+        self.saved = saved;
+        self
+    }
+
+    pub fn unread(mut self, unread: bool) -> Self {
+        self.unread = unread;
+        self
+    }
+
     pub fn from_command(cmd: &str) -> Option<Self> {
-        if cmd.starts_with(VIEW_CMD) {
+        if cmd == RANDOM_CMD {
+            Some(Self::new(LessonStatusRange::Approved, None).mode(ReadMode::Random))
+        } else if cmd == DAILY_CMD {
+            Some(Self::new(LessonStatusRange::Approved, None).mode(ReadMode::Daily))
+        } else if cmd == SAVED_CMD {
+            Some(Self::new(LessonStatusRange::Approved, None).saved(true))
+        } else if cmd.starts_with(VIEW_CMD) {
             let mut parts = cmd.split_whitespace();
             parts.next();
-            let status_range = if let Some(s) = parts.next() {
-                LessonStatusRange::from_str(s).ok()?
+            let mut opts = if let Some(s) = parts.next() {
+                Self::from_view(s)?
             } else {
                 Default::default()
             };
-            let prev_lesson = if let Some(s) = parts.next() {
-                Some(s.parse().ok()?)
-            } else {
-                Default::default()
-            };
-            Some(Self {
-                status_range,
-                prev_lesson,
-            })
+            if let Some(s) = parts.next() {
+                let (s, backward) = s.strip_prefix('<').map_or((s, false), |s| (s, true));
+                opts.prev_lesson = Some(s.parse().ok()?);
+                opts.backward = backward;
+            }
+            if opts.mode == ReadMode::Random {
+                for recent in &mut opts.recent {
+                    *recent = parts.next().map(str::parse).transpose().ok()?;
+                }
+            }
+            Some(opts)
         } else {
             None
         }
@@ -87,88 +258,418 @@ impl LessonReadOptions {
 
     pub fn to_command(&self) -> String {
         if let Some(prev) = self.prev_lesson {
-            format!("{VIEW_CMD} {} {prev}", self.status_range.as_ref())
+            let dir = if self.backward { "<" } else { "" };
+            let mut cmd = format!("{VIEW_CMD} {} {dir}{prev}", self.view());
+            for recent in self.recent.iter().flatten() {
+                write!(cmd, " {recent}").ok();
+            }
+            cmd
         } else {
-            format!("{VIEW_CMD} {}", self.status_range.as_ref())
+            format!("{VIEW_CMD} {}", self.view())
         }
     }
 
+    /// The options to read on after the lesson, the random mode remembers the recent ones
+    fn after(&self, lesson_id: i32) -> Self {
+        let recent = match self.mode {
+            ReadMode::Random if self.prev_lesson == Some(lesson_id) => self.recent,
+            ReadMode::Random => {
+                let mut recent = [self.prev_lesson; RECENT_LESSONS];
+                recent[1..].copy_from_slice(&self.recent[..RECENT_LESSONS - 1]);
+                recent
+            }
+            _ => [None; RECENT_LESSONS],
+        };
+        Self {
+            prev_lesson: Some(lesson_id),
+            backward: false,
+            recent,
+            ..*self
+        }
+    }
+
+    /// The same view from the start in another order, the keyset cursor is for the old one
+    fn sorted_by(&self, sort: SortOrder) -> Self {
+        Self {
+            sort,
+            prev_lesson: None,
+            backward: false,
+            ..*self
+        }
+    }
+
+    /// Parses a view token, either a named one typed by users like `best+own+oldest`
+    /// or a compact one from buttons like `boO`
+    pub(crate) fn from_view(s: &str) -> Option<Self> {
+        Self::from_named_view(s).or_else(|| Self::from_compact_view(s))
+    }
+
+    fn from_named_view(s: &str) -> Option<Self> {
+        let mut parts = s.split('+');
+        let status_range = LessonStatusRange::from_str(parts.next()?).ok()?;
+        let mut opts = Self::new(status_range, None);
+        for flag in parts {
+            match flag {
+                OWN_LANG_FLAG => opts.own_lang = true,
+                CAROUSEL_FLAG => opts.carousel = true,
+                SAVED_FLAG => opts.saved = true,
+                UNREAD_FLAG => opts.unread = true,
+                _ => match ReadMode::from_str(flag) {
+                    Ok(mode) => opts.mode = mode,
+                    Err(_) => opts.sort = SortOrder::from_str(flag).ok()?,
+                },
+            }
+        }
+        Some(opts)
+    }
+
+    /// A status range letter followed by flag letters: `o`wn language, `c`arousel, `s`aved,
+    /// `u`nread, `R`andom and `D`aily modes, `O`ldest first and best first by `S`core
+    fn from_compact_view(s: &str) -> Option<Self> {
+        let mut chars = s.chars();
+        let status_range = LessonStatusRange::from_code(chars.next()?)?;
+        let mut opts = Self::new(status_range, None);
+        for flag in chars {
+            match flag {
+                'o' => opts.own_lang = true,
+                'c' => opts.carousel = true,
+                's' => opts.saved = true,
+                'u' => opts.unread = true,
+                'R' => opts.mode = ReadMode::Random,
+                'D' => opts.mode = ReadMode::Daily,
+                'O' => opts.sort = SortOrder::Oldest,
+                'S' => opts.sort = SortOrder::Score,
+                _ => return None,
+            }
+        }
+        Some(opts)
+    }
+
+    /// Formats the options except the position as a compact view token, buttons data is
+    /// limited to 64 bytes
+    pub(crate) fn view(&self) -> String {
+        let mut view = String::from(self.status_range.code());
+        if self.own_lang {
+            view.push('o');
+        }
+        if self.carousel {
+            view.push('c');
+        }
+        if self.saved {
+            view.push('s');
+        }
+        if self.unread {
+            view.push('u');
+        }
+        match self.mode {
+            ReadMode::Sequential => {}
+            ReadMode::Random => view.push('R'),
+            ReadMode::Daily => view.push('D'),
+        }
+        match self.sort {
+            SortOrder::Newest => {}
+            SortOrder::Oldest => view.push('O'),
+            SortOrder::Score => view.push('S'),
+        }
+        view
+    }
+
+    /// Whether the next lesson has a lower sort key than the previous one
+    fn descending(&self) -> bool {
+        (self.sort != SortOrder::Oldest) != self.backward
+    }
+
+    /// Language codes of lessons to show, `None` for all languages
+    fn langs(&self, lang: Lang) -> Option<Vec<&'static str>> {
+        self.own_lang.then(|| {
+            let mut langs = lang.codes();
+            langs.push(UNDETERMINED_LANG);
+            langs
+        })
+    }
+
     pub fn prev_lesson(&mut self, id: impl Into<i32>) -> &Self {
         self.prev_lesson = Some(id.into());
         self
     }
 
+    /// Sends the next lesson, the carousel edits the message with the pressed button instead
     pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
-        match Lesson::get(pool, self.status_range, self.prev_lesson).await {
+        let is_moderator = repl.is_moderator();
+        let reader = repl.reader_key();
+        let reader = reader.as_deref();
+        let moderator = repl.sender_id().filter(|_| is_moderator);
+        let (text, keyboard) = match Lesson::get(pool, self, repl.lang, reader, moderator).await {
             Ok(Some(lesson)) => {
-                repl.send_text(lesson.message(repl.is_moderator()))
+                if let Some(reader) = reader {
+                    mark_read(pool, reader, lesson.id).await;
+                }
+                let position = lesson
+                    .carousel_position(pool, self, repl.lang, reader)
+                    .await;
+                (
+                    lesson.message(is_moderator, false),
+                    lesson.keyboard(self, repl.lang, is_moderator, false, position),
+                )
+            }
+            Ok(None) => (
+                TEXT.no_more_lessons.to(repl.lang),
+                start_keyboard(pool, repl.lang, is_moderator, self.unread).await,
+            ),
+            Err(e) => {
+                repl.send_text(internal_error(&e)).await?;
+                return Ok(());
+            }
+        };
+        if self.carousel && repl.callback {
+            repl.edit_or_send(text, keyboard).await
+        } else {
+            repl.send_text(text).reply_markup(keyboard).await?;
+            Ok(())
+        }
+    }
+}
+
+/// Enables the share button, deep links need the bot username
+pub fn set_bot_username(username: &str) {
+    BOT_USERNAME.set(username.to_owned()).ok();
+}
+
+/// Drops the bot username groups add to commands, `/approve@bot_username 12`
+pub fn strip_bot_mention(cmd: &str) -> Cow<'_, str> {
+    match BOT_USERNAME.get() {
+        Some(username) => strip_mention(cmd, username),
+        None => Cow::Borrowed(cmd),
+    }
+}
+
+fn strip_mention<'a>(cmd: &'a str, username: &str) -> Cow<'a, str> {
+    let end = cmd.find(char::is_whitespace).unwrap_or(cmd.len());
+    match cmd[..end].split_once('@') {
+        Some((name, mention)) if mention.eq_ignore_ascii_case(username) => {
+            Cow::Owned(format!("{name}{}", &cmd[end..]))
+        }
+        _ => Cow::Borrowed(cmd),
+    }
+}
+
+impl ShowLesson {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let (name, arg) = cmd.split_once(char::is_whitespace)?;
+        let lesson_id = match name {
+            LESSON_CMD => arg.trim(),
+            START_CMD => arg.trim().strip_prefix(DEEP_LINK_PREFIX)?,
+            _ => return None,
+        };
+        Some(Self {
+            lesson_id: lesson_id.parse().ok()?,
+        })
+    }
+
+    /// Readers see only approved and best lessons, moderators see any
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let is_moderator = repl.is_moderator();
+        let reader = repl.reader_key();
+        match Lesson::find(pool, self.lesson_id, repl.lang, reader.as_deref()).await {
+            Ok(Some(lesson)) if is_moderator || lesson.is_public() => {
+                if let Some(reader) = &reader {
+                    mark_read(pool, reader, lesson.id).await;
+                }
+                let view = LessonReadOptions::default();
+                repl.send_text(lesson.message(is_moderator, false))
+                    .reply_markup(lesson.keyboard(&view, repl.lang, is_moderator, false, None))
+                    .await?
+            }
+            Ok(_) => repl.send_text(&TEXT.lesson_not_found).await?,
+            Err(e) => repl.send_text(internal_error(&e)).await?,
+        };
+        Ok(())
+    }
+}
+
+impl TranslateLesson {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let (name, args) = cmd.split_once(char::is_whitespace)?;
+        if name != TRANSLATE_CMD {
+            return None;
+        }
+        let args = args.trim_start();
+        let (lesson_id, args) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+        let lesson_id = lesson_id.parse().ok()?;
+        let args = args.trim_start();
+        let translation = match args.split_once(char::is_whitespace) {
+            Some((lang, text)) if !text.trim().is_empty() => {
+                Some((LANGS.get(lang)?, text.trim().to_owned()))
+            }
+            _ => None,
+        };
+        Some(Self {
+            lesson_id,
+            translation,
+        })
+    }
+
+    pub(crate) fn to_command(&self) -> String {
+        format!("{TRANSLATE_CMD} {}", self.lesson_id)
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let (lang, text) = match &self.translation {
+            Some(translation) => translation,
+            None => {
+                let langs: Vec<_> = LANGS.all().map(Lang::code).collect();
+                repl.send_text(format!(
+                    "To translate the lesson send:\n{TRANSLATE_CMD} {} <lang> <text>\n\n\
+                    Languages: {}",
+                    self.lesson_id,
+                    langs.join(", "),
+                ))
+                .await?;
+                return Ok(());
+            }
+        };
+        match Lesson::translate(pool, self.lesson_id, *lang, text).await {
+            Ok(true) => {
+                repl.send_text(format!(
+                    "✅ Translation to {} saved for lesson {}",
+                    lang.code(),
+                    self.lesson_id
+                ))
+                .await?
+            }
+            Ok(false) => repl.send_text(&TEXT.lesson_not_found).await?,
+            Err(e) => repl.send_text(internal_error(&e)).await?,
+        };
+        Ok(())
+    }
+}
+
+impl SaveLesson {
+    fn new(view: LessonReadOptions, lesson_id: i32, save: bool) -> Self {
+        Self {
+            view,
+            lesson_id,
+            save,
+            original: false,
+        }
+    }
+
+    fn original(mut self, original: bool) -> Self {
+        self.original = original;
+        self
+    }
+
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let mut parts = cmd.split_whitespace();
+        let save = match parts.next()? {
+            SAVE_CMD => true,
+            UNSAVE_CMD => false,
+            _ => return None,
+        };
+        let view = LessonReadOptions::from_view(parts.next()?)?;
+        let lesson_id = parts.next()?.parse().ok()?;
+        let original = match parts.next() {
+            Some("original") => true,
+            Some(_) => return None,
+            None => false,
+        };
+        Some(Self::new(view, lesson_id, save).original(original))
+    }
+
+    fn to_command(&self) -> String {
+        let cmd = if self.save { SAVE_CMD } else { UNSAVE_CMD };
+        let original = if self.original { " original" } else { "" };
+        format!("{cmd} {} {}{original}", self.view.view(), self.lesson_id)
+    }
+
+    /// Updates the bookmark and the button on the lesson message
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let reader = match repl.reader_key() {
+            Some(reader) => reader,
+            None => return Ok(()),
+        };
+        let res = async {
+            Lesson::save(pool, self.lesson_id, &reader, self.save).await?;
+            Lesson::find(pool, self.lesson_id, repl.lang, Some(&reader)).await
+        };
+        match res.await {
+            Ok(Some(lesson)) => {
+                let position = lesson
+                    .carousel_position(pool, &self.view, repl.lang, Some(&reader))
+                    .await;
+                repl.edit_text(lesson.message(repl.is_moderator(), self.original))
                     .reply_markup(lesson.keyboard(
-                        self.status_range,
+                        &self.view,
                         repl.lang,
                         repl.is_moderator(),
+                        self.original,
+                        position,
                     ))
                     .await?
             }
-            Ok(None) => {
-                repl.send_text(TEXT.no_more_lessons.to(repl.lang))
-                    .reply_markup(start_keyboard(pool, repl.lang, repl.is_moderator()).await)
-                    .await?
-            }
+            Ok(None) => repl.send_text(&TEXT.lesson_not_found).await?,
             Err(e) => repl.send_text(internal_error(&e)).await?,
         };
         Ok(())
     }
 }
 
-impl SetLessonStatus {
-    fn new(status_range: LessonStatusRange, lesson_id: i32, status: LessonStatus) -> Self {
+impl ToggleTranslation {
+    fn new(view: LessonReadOptions, lesson_id: i32, original: bool) -> Self {
         Self {
-            status_range,
+            view,
             lesson_id,
-            status,
+            original,
         }
     }
 
     pub fn from_command(cmd: &str) -> Option<Self> {
-        if cmd.starts_with(SET_STATUS_CMD) {
-            let mut parts = cmd.split_whitespace();
-            parts.next();
-            let status_range = parts
-                .next()
-                .and_then(|s| LessonStatusRange::from_str(s).ok())?;
-            let lesson_id = parts.next().and_then(|s| s.parse().ok())?;
-            let status = parts.next().and_then(|s| LessonStatus::from_str(s).ok())?;
-            Some(Self {
-                status_range,
-                lesson_id,
-                status,
-            })
-        } else {
-            None
+        let mut parts = cmd.split_whitespace();
+        if parts.next()? != TOGGLE_TRANSLATION_CMD {
+            return None;
         }
+        let view = LessonReadOptions::from_view(parts.next()?)?;
+        let lesson_id = parts.next()?.parse().ok()?;
+        let original = match parts.next()? {
+            "original" => true,
+            "translation" => false,
+            _ => return None,
+        };
+        Some(Self::new(view, lesson_id, original))
     }
 
     fn to_command(&self) -> String {
         format!(
-            "{} {} {} {}",
-            SET_STATUS_CMD,
-            self.status_range.as_ref(),
+            "{TOGGLE_TRANSLATION_CMD} {} {} {}",
+            self.view.view(),
             self.lesson_id,
-            self.status.as_ref()
+            if self.original {
+                "original"
+            } else {
+                "translation"
+            }
         )
     }
 
     pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
-        match Lesson::set_status(pool, self.lesson_id, self.status).await {
-            Ok(lesson) => {
-                repl.edit_text(lesson.message(repl.is_moderator()))
+        let reader = repl.reader_key();
+        let reader = reader.as_deref();
+        match Lesson::find(pool, self.lesson_id, repl.lang, reader).await {
+            Ok(Some(lesson)) => {
+                let position = lesson
+                    .carousel_position(pool, &self.view, repl.lang, reader)
+                    .await;
+                repl.edit_text(lesson.message(repl.is_moderator(), self.original))
                     .reply_markup(lesson.keyboard(
-                        self.status_range,
+                        &self.view,
                         repl.lang,
                         repl.is_moderator(),
+                        self.original,
+                        position,
                     ))
                     .await?
             }
+            Ok(None) => repl.send_text(&TEXT.lesson_not_found).await?,
             Err(e) => repl.send_text(internal_error(&e)).await?,
         };
         Ok(())
@@ -176,118 +677,548 @@ impl SetLessonStatus {
 }
 
 impl Lesson {
-    /// Returns a lesson to read after the `prev` lesson with a minimal status `min_status`
-    async fn get(
+    /// Returns a lesson to read after the `prev` lesson with a minimal status `min_status`.
+    /// Random and daily modes skip the `prev` lesson and prefer best lessons, the random one
+    /// shows the recent lessons only when nothing else is left. A new lesson is claimed for
+    /// the `moderator`, those others claim meanwhile are skipped.
+    pub(crate) async fn get(
+        pool: &PgPool,
+        opts: &LessonReadOptions,
+        lang: Lang,
+        reader: Option<&str>,
+        moderator: Option<i64>,
+    ) -> Result<Option<Self>, Error> {
+        let statuses = opts.status_range.statuses();
+        let prev = opts.prev_lesson;
+        let langs = opts.langs(lang);
+        // A lost claim is another moderator's won one, the next query skips that lesson
+        loop {
+            let lesson = query!(
+                r#"
+                SELECT l.id, l.status as "status: LessonStatus"
+                FROM lesson l
+                LEFT JOIN bookmark b ON b.lesson_id = l.id AND b.reader = $9
+                WHERE l.status = ANY($1)
+                  AND l.deleted_at IS NULL
+                  AND ($2::int IS NULL OR CASE
+                      WHEN $4 <> 'sequential' THEN l.id <> $2
+                      WHEN $6 THEN (CASE WHEN $7 = 'score' THEN l.status ELSE 'new' END, l.id)
+                          < (SELECT CASE WHEN $7 = 'score' THEN status ELSE 'new' END, id
+                             FROM lesson WHERE id = $2)
+                      ELSE (CASE WHEN $7 = 'score' THEN l.status ELSE 'new' END, l.id)
+                          > (SELECT CASE WHEN $7 = 'score' THEN status ELSE 'new' END, id
+                             FROM lesson WHERE id = $2)
+                  END)
+                  AND ($3::text[] IS NULL OR COALESCE(l.lang, 'und') = ANY($3))
+                  AND (NOT $8 OR b.reader IS NOT NULL)
+                  AND (NOT $10 OR NOT EXISTS (
+                      SELECT 1 FROM lesson_read r WHERE r.lesson_id = l.id AND r.reader = $9
+                  ))
+                  AND ($11::bigint IS NULL
+                    OR l.status <> 'new'
+                    OR l.claimed_by IS NULL
+                    OR l.claimed_by = $11
+                    OR l.claimed_at < now() - $12::float8 * interval '1 second')
+                ORDER BY
+                    CASE WHEN $4 = 'random' THEN l.id = ANY($13) END,
+                    CASE WHEN $4 = 'random' THEN
+                        -ln(1 - random()) / CASE WHEN l.status = 'best' THEN $5::float8 ELSE 1 END
+                    END,
+                    -- The same weighted draw with a uniform number from a hash of the day
+                    CASE WHEN $4 = 'daily' THEN
+                        -ln(1 - ('x' || left(md5(l.id || ':' || current_date), 8))::bit(32)::bigint
+                            / 4294967296.0)::float8
+                        / CASE WHEN l.status = 'best' THEN $5::float8 ELSE 1 END
+                    END,
+                    CASE WHEN $7 = 'score' AND $6 THEN l.status END DESC,
+                    CASE WHEN $7 = 'score' AND NOT $6 THEN l.status END,
+                    CASE WHEN $6 THEN l.id END DESC,
+                    l.id
+                LIMIT 1
+                "#,
+                statuses as &[LessonStatus],
+                prev,
+                langs.as_deref() as Option<&[&str]>,
+                opts.mode.as_ref(),
+                BEST_WEIGHT,
+                opts.descending(),
+                opts.sort.as_ref(),
+                opts.saved,
+                reader,
+                opts.unread,
+                moderator,
+                CONF.claim_timeout.as_secs_f64(),
+                &opts.recent.iter().flatten().copied().collect::<Vec<_>>(),
+            )
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| Error::ReadNextLesson(e, opts.status_range, prev))?;
+            let lesson_id = match (lesson, moderator) {
+                (None, _) => return Ok(None),
+                (Some(lesson), Some(moderator)) if lesson.status == LessonStatus::New => {
+                    if !Self::claim(pool, lesson.id, moderator).await? {
+                        // Another moderator has just claimed it, try the next one
+                        continue;
+                    }
+                    lesson.id
+                }
+                (Some(lesson), _) => lesson.id,
+            };
+            return Self::find(pool, lesson_id, lang, reader).await;
+        }
+    }
+
+    /// Returns the 1-based position of the lesson in the sort order and the total number of
+    /// lessons
+    async fn position(
+        &self,
         pool: &PgPool,
-        status_range: LessonStatusRange,
-        prev: Option<i32>,
+        opts: &LessonReadOptions,
+        lang: Lang,
+        reader: Option<&str>,
+    ) -> Result<(i64, i64), Error> {
+        let statuses = opts.status_range.statuses();
+        let forward = LessonReadOptions {
+            backward: false,
+            ..*opts
+        };
+        let key = if opts.sort == SortOrder::Score {
+            self.status
+        } else {
+            LessonStatus::New
+        };
+        let row = query!(
+            r#"
+            SELECT
+                count(*) FILTER (WHERE CASE
+                    WHEN $4 THEN (CASE WHEN $5 = 'score' THEN status ELSE 'new' END, id)
+                        >= ($6::lesson_status, $2)
+                    ELSE (CASE WHEN $5 = 'score' THEN status ELSE 'new' END, id)
+                        <= ($6::lesson_status, $2)
+                END) as "position!",
+                count(*) as "total!"
+            FROM lesson
+            WHERE status = ANY($1)
+              AND deleted_at IS NULL
+              AND ($3::text[] IS NULL OR COALESCE(lang, 'und') = ANY($3))
+              AND (NOT $7 OR EXISTS (
+                  SELECT 1 FROM bookmark b WHERE b.lesson_id = lesson.id AND b.reader = $8
+              ))
+              AND (NOT $9 OR NOT EXISTS (
+                  SELECT 1 FROM lesson_read r WHERE r.lesson_id = lesson.id AND r.reader = $8
+              ))
+            "#,
+            statuses as &[LessonStatus],
+            self.id,
+            opts.langs(lang) as Option<Vec<&str>>,
+            forward.descending(),
+            opts.sort.as_ref(),
+            key as LessonStatus,
+            opts.saved,
+            reader,
+            opts.unread,
+        )
+        .fetch_one(pool)
+        .await
+        .map_err(|e| Error::LessonPosition(e, self.id))?;
+        Ok((row.position, row.total))
+    }
+
+    /// The position shown in the sequential carousel, `None` elsewhere or on errors
+    async fn carousel_position(
+        &self,
+        pool: &PgPool,
+        view: &LessonReadOptions,
+        lang: Lang,
+        reader: Option<&str>,
+    ) -> Option<(i64, i64)> {
+        if !view.carousel || view.mode != ReadMode::Sequential {
+            return None;
+        }
+        self.position(pool, view, lang, reader)
+            .await
+            .map_err(|e| log_error(&e))
+            .ok()
+    }
+
+    /// Returns a lesson by its id, the only query reading whole lessons
+    pub(crate) async fn find(
+        pool: &PgPool,
+        lesson_id: i32,
+        lang: Lang,
+        reader: Option<&str>,
     ) -> Result<Option<Self>, Error> {
-        let (min_status, max_status) = status_range.range();
         query_as!(
             Self,
             r#"
-            SELECT 
-                id,
-                text,
-                status as "status: _",
-                created_at
-            FROM lesson 
-            WHERE status >= $1
-              AND status <= $2
-              AND ($3::int IS NULL OR id < $3)
-            ORDER BY id DESC
-            LIMIT 1
+            SELECT
+                l.id,
+                l.text,
+                l.status as "status: _",
+                l.created_at,
+                l.lang,
+                l.reason,
+                l.original_text,
+                ARRAY(
+                    SELECT result_id FROM lesson_lineage WHERE source_id = l.id ORDER BY result_id
+                ) as "superseded_by!",
+                t.text as "translation?",
+                CASE WHEN $3::text IS NULL THEN NULL ELSE b.reader IS NOT NULL END as "saved?",
+                (SELECT count(*) FROM best_vote v WHERE v.lesson_id = l.id) as "best_votes!",
+                ARRAY(
+                    SELECT n.author_name || ': ' || n.text FROM lesson_note n
+                    WHERE n.lesson_id = l.id ORDER BY n.id DESC LIMIT 3
+                ) as "notes!"
+            FROM lesson l
+            LEFT JOIN lesson_translation t ON t.lesson_id = l.id AND t.lang = $2
+            LEFT JOIN bookmark b ON b.lesson_id = l.id AND b.reader = $3
+            WHERE l.id = $1 AND l.deleted_at IS NULL
             "#,
-            min_status as LessonStatus,
-            max_status as LessonStatus,
-            prev,
+            lesson_id,
+            lang.code(),
+            reader,
         )
         .fetch_optional(pool)
         .await
-        .map_err(|e| Error::ReadNextLesson(e, min_status, prev))
+        .map_err(|e| Error::FindLesson(e, lesson_id))
     }
 
-    async fn set_status(
+    /// Adds the lesson to the reader's bookmarks or removes it
+    async fn save(pool: &PgPool, lesson_id: i32, reader: &str, save: bool) -> Result<(), Error> {
+        if save {
+            query!(
+                r#"
+                INSERT INTO bookmark (reader, lesson_id) VALUES ($1, $2)
+                ON CONFLICT (reader, lesson_id) DO NOTHING
+                "#,
+                reader,
+                lesson_id,
+            )
+            .execute(pool)
+            .await
+        } else {
+            query!(
+                "DELETE FROM bookmark WHERE reader = $1 AND lesson_id = $2",
+                reader,
+                lesson_id,
+            )
+            .execute(pool)
+            .await
+        }
+        .map(|_| ())
+        .map_err(|e| Error::SaveLesson(e, lesson_id))
+    }
+
+    /// Saves a translation of the lesson, returns `false` if there is no such lesson
+    async fn translate(
         pool: &PgPool,
         lesson_id: i32,
-        status: LessonStatus,
-    ) -> Result<Self, Error> {
-        query_as!(
-            Self,
+        lang: Lang,
+        text: &str,
+    ) -> Result<bool, Error> {
+        query!(
             r#"
-            UPDATE lesson
-            SET status=$1
-            WHERE id=$2
-            RETURNING
-                id,
-                text,
-                status as "status: _",
-                created_at
+            INSERT INTO lesson_translation (lesson_id, lang, text)
+            SELECT id, $2, $3 FROM lesson WHERE id = $1 AND deleted_at IS NULL
+            ON CONFLICT (lesson_id, lang) DO UPDATE SET text = $3, created_at = now()
             "#,
-            status as LessonStatus,
-            lesson_id
+            lesson_id,
+            lang.code(),
+            text,
         )
-        .fetch_one(pool)
+        .execute(pool)
         .await
-        .map_err(|e| Error::SetLessonStatus(e, lesson_id, status))
+        .map(|done| done.rows_affected() > 0)
+        .map_err(|e| Error::TranslateLesson(e, lesson_id, lang))
     }
 
-    fn keyboard(
+    /// Reader buttons, the `position` and total lessons number are shown in the carousel
+    pub(crate) fn keyboard(
         &self,
-        status_range: LessonStatusRange,
+        view: &LessonReadOptions,
         lang: Lang,
         is_moderator: bool,
+        original: bool,
+        position: Option<(i64, i64)>,
     ) -> InlineKeyboardMarkup {
-        let mut line = vec![InlineKeyboardButton::callback(
-            TEXT.next_lesson.to(lang),
-            LessonReadOptions::new(status_range, Some(self.id)).to_command(),
-        )];
-        if is_moderator {
-            for (status, label) in [
-                (LessonStatus::Approved, "👍 Approve"),
-                (LessonStatus::Rejected, "👎 Reject"),
-                (LessonStatus::Best, "🏆 Mark best"),
-            ] {
-                if self.status != status {
-                    line.push(InlineKeyboardButton::callback(
-                        label,
-                        SetLessonStatus::new(status_range, self.id, status).to_command(),
-                    ));
-                }
-            }
+        let next = view.after(self.id);
+        let lang_filter = if view.own_lang {
+            TEXT.all_languages.to(lang)
+        } else {
+            TEXT.own_language.to(lang)
+        };
+        let (next_label, next) = match view.mode {
+            ReadMode::Sequential => (TEXT.next_lesson.to(lang), next),
+            ReadMode::Random => (TEXT.another_random.to(lang), next),
+            ReadMode::Daily => (TEXT.random_lesson.to(lang), next.mode(ReadMode::Random)),
+        };
+        let mut lines = vec![];
+        let mut line = vec![];
+        if view.carousel && view.mode == ReadMode::Sequential {
+            lines.push(self.carousel_buttons(view, position));
+        } else {
+            line.push(InlineKeyboardButton::callback(
+                next_label,
+                next.to_command(),
+            ));
+        }
+        line.push(InlineKeyboardButton::callback(
+            lang_filter,
+            next.own_lang(!view.own_lang).to_command(),
+        ));
+        if self.translation.is_some() {
+            let label = if original {
+                TEXT.show_translation.to(lang)
+            } else {
+                TEXT.show_original.to(lang)
+            };
+            line.push(InlineKeyboardButton::callback(
+                label,
+                ToggleTranslation::new(*view, self.id, !original).to_command(),
+            ));
         }
         line.push(InlineKeyboardButton::callback(TEXT.help.to(lang), "/help"));
-        InlineKeyboardMarkup::new(vec![line])
+        lines.push(line);
+        let mut line = vec![];
+        if view.mode == ReadMode::Sequential {
+            let (label, sort) = match view.sort {
+                SortOrder::Newest => (&TEXT.sort_oldest, SortOrder::Oldest),
+                SortOrder::Oldest => (&TEXT.sort_score, SortOrder::Score),
+                SortOrder::Score => (&TEXT.sort_newest, SortOrder::Newest),
+            };
+            line.push(InlineKeyboardButton::callback(
+                label.to(lang),
+                view.sorted_by(sort).to_command(),
+            ));
+        }
+        if let Some(saved) = self.saved {
+            let label = if saved {
+                &TEXT.unsave_lesson
+            } else {
+                &TEXT.save_lesson
+            };
+            line.push(InlineKeyboardButton::callback(
+                label.to(lang),
+                SaveLesson::new(*view, self.id, !saved)
+                    .original(original)
+                    .to_command(),
+            ));
+        }
+        if let Some(url) = self.share_url() {
+            line.push(InlineKeyboardButton::url(TEXT.share.to(lang), url));
+        }
+        if !line.is_empty() {
+            lines.push(line);
+        }
+        if is_moderator {
+            lines.extend(self.moderator_buttons(view));
+        }
+        InlineKeyboardMarkup::new(lines)
+    }
+
+    /// ⏮ ◀️ 12 / 340 ▶️, the first lesson has no buttons to go back
+    fn carousel_buttons(
+        &self,
+        view: &LessonReadOptions,
+        position: Option<(i64, i64)>,
+    ) -> Vec<InlineKeyboardButton> {
+        let mut buttons = vec![];
+        if position.is_none_or(|(position, _)| position > 1) {
+            let first = LessonReadOptions {
+                prev_lesson: None,
+                backward: false,
+                ..*view
+            };
+            let prev = LessonReadOptions {
+                prev_lesson: Some(self.id),
+                backward: true,
+                ..*view
+            };
+            buttons.push(InlineKeyboardButton::callback("⏮", first.to_command()));
+            buttons.push(InlineKeyboardButton::callback("◀️", prev.to_command()));
+        }
+        if let Some((position, total)) = position {
+            buttons.push(InlineKeyboardButton::callback(
+                format!("{position} / {total}"),
+                NOOP_CALLBACK,
+            ));
+        }
+        let next = LessonReadOptions {
+            prev_lesson: Some(self.id),
+            backward: false,
+            ..*view
+        };
+        buttons.push(InlineKeyboardButton::callback("▶️", next.to_command()));
+        buttons
+    }
+
+    pub(crate) fn is_public(&self) -> bool {
+        self.status.is_public()
+    }
+
+    /// A telegram share dialog with the lesson deep link, hidden lessons aren't shared
+    fn share_url(&self) -> Option<Url> {
+        if !self.is_public() {
+            return None;
+        }
+        let link = lesson_link(self.id)?;
+        Url::parse_with_params("https://t.me/share/url", [("url", link)]).ok()
     }
 
-    fn message(&self, is_moderator: bool) -> String {
+    /// The lesson text, a translation is shown unless the `original` is requested
+    pub(crate) fn message(&self, is_moderator: bool, original: bool) -> String {
+        let (text, translated) = match &self.translation {
+            Some(translation) if !original => (translation, true),
+            _ => (&self.text, false),
+        };
         if is_moderator {
-            format!(
-                "{}\n\nid: {}, status: {}, created: {} ago",
-                self.text,
+            let mut message = format!(
+                "{}\n\nid: {}, status: {}{}, lang: {}, created: {} ago{}",
+                text,
                 self.id,
                 self.status.as_ref(),
+                self.reason
+                    .as_ref()
+                    .map(|reason| format!(" ({reason})"))
+                    .unwrap_or_default(),
+                self.lang.as_deref().unwrap_or(UNDETERMINED_LANG),
                 timeago(self.created_at),
-            )
+                if translated { ", translated" } else { "" },
+            );
+            if !self.superseded_by.is_empty() {
+                write!(
+                    message,
+                    "\n🔀 superseded by {}",
+                    join_ids(&self.superseded_by)
+                )
+                .ok();
+            }
+            if let Some(original) = &self.original_text {
+                write!(message, "\n✏️ edited: {}", word_diff(original, &self.text)).ok();
+            }
+            for note in &self.notes {
+                write!(message, "\n📝 {note}").ok();
+            }
+            message
         } else {
-            self.text.to_owned()
+            text.to_owned()
+        }
+    }
+}
+
+impl LessonStatus {
+    const ALL: [Self; 9] = [
+        Self::Superseded,
+        Self::Withdrawn,
+        Self::Archived,
+        Self::Duplicate,
+        Self::Rejected,
+        Self::NeedsEdit,
+        Self::New,
+        Self::Approved,
+        Self::Best,
+    ];
+
+    /// The states a lesson in this one may move to
+    pub(crate) fn transitions(self) -> &'static [Self] {
+        use LessonStatus::*;
+        match self {
+            New => &[
+                NeedsEdit, Approved, Best, Rejected, Duplicate, Superseded, Withdrawn,
+            ],
+            NeedsEdit => &[
+                New, Approved, Best, Rejected, Duplicate, Superseded, Withdrawn,
+            ],
+            Approved => &[
+                Best, NeedsEdit, Rejected, Duplicate, Archived, Superseded, Withdrawn,
+            ],
+            Best => &[Approved, Archived, Withdrawn],
+            Rejected => &[New, Approved, Best, Duplicate, Superseded],
+            Duplicate => &[New, Approved, Rejected],
+            Archived => &[Approved, Withdrawn],
+            Superseded | Withdrawn => &[],
+        }
+    }
+
+    pub(crate) fn can_become(self, status: Self) -> bool {
+        self.transitions().contains(&status)
+    }
+
+    /// The states a lesson may move to `status` from
+    pub(crate) fn sources(status: Self) -> Vec<Self> {
+        Self::ALL
+            .into_iter()
+            .filter(|s| s.can_become(status))
+            .collect()
+    }
+
+    /// Readers see lessons in these states
+    pub(crate) fn is_public(self) -> bool {
+        matches!(self, Self::Approved | Self::Best)
+    }
+
+    /// The status as a moderator's decision
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Self::Superseded => "🔀 Superseded",
+            Self::Withdrawn => "🚫 Withdrawn",
+            Self::Archived => "📦 Archived",
+            Self::Duplicate => "♊ Duplicate",
+            Self::NeedsEdit => "🛠 Needs edit",
+            Self::Rejected => "👎 Rejected",
+            Self::New => "🆕 New",
+            Self::Approved => "👍 Approved",
+            Self::Best => "🏆 Best",
         }
     }
 }
 
 impl LessonStatusRange {
-    fn range(self) -> (LessonStatus, LessonStatus) {
+    /// A letter for the compact view token
+    fn code(self) -> char {
         match self {
-            Self::Rejected => (LessonStatus::Rejected, LessonStatus::Rejected),
-            Self::New => (LessonStatus::New, LessonStatus::New),
-            Self::Approved => (LessonStatus::Approved, LessonStatus::Best),
-            Self::Best => (LessonStatus::Best, LessonStatus::Best),
-            Self::All => (LessonStatus::New, LessonStatus::Best),
+            Self::Rejected => 'r',
+            Self::New => 'n',
+            Self::Approved => 'a',
+            Self::Best => 'b',
+            Self::All => 'x',
+        }
+    }
+
+    fn from_code(code: char) -> Option<Self> {
+        [
+            Self::Rejected,
+            Self::New,
+            Self::Approved,
+            Self::Best,
+            Self::All,
+        ]
+        .into_iter()
+        .find(|range| range.code() == code)
+    }
+
+    fn statuses(self) -> &'static [LessonStatus] {
+        use LessonStatus::*;
+        match self {
+            Self::Rejected => &[Rejected, Duplicate],
+            Self::New => &[New, NeedsEdit],
+            Self::Approved => &[Approved, Best],
+            Self::Best => &[Best],
+            Self::All => &[New, Approved, Best],
         }
     }
 }
 
-fn timeago(dt: OffsetDateTime) -> String {
+/// A deep link opening the lesson in the bot
+pub(crate) fn lesson_link(lesson_id: i32) -> Option<Url> {
+    Url::parse(&deep_link(BOT_USERNAME.get()?, lesson_id)).ok()
+}
+
+fn deep_link(username: &str, lesson_id: i32) -> String {
+    format!("https://t.me/{username}?start={DEEP_LINK_PREFIX}{lesson_id}")
+}
+
+pub(crate) fn timeago(dt: OffsetDateTime) -> String {
     humantime::format_duration((OffsetDateTime::now_utc() - dt).unsigned_abs())
         .to_string()
         .split_whitespace()
@@ -299,6 +1230,7 @@ fn timeago(dt: OffsetDateTime) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{config::MAX_REJECTION_REASON_LEN, RejectionReasons, SetLessonStatus};
 
     #[test]
     fn lesson_read_options_from_command() {
@@ -327,27 +1259,270 @@ mod tests {
             LessonReadOptions::from_command("/view best 35").unwrap(),
             LessonReadOptions::new(LessonStatusRange::Best, Some(35))
         );
+        assert_eq!(
+            LessonReadOptions::from_command("/view best+own 35").unwrap(),
+            LessonReadOptions::new(LessonStatusRange::Best, Some(35)).own_lang(true)
+        );
+        assert!(LessonReadOptions::from_command("/view best+foo").is_none());
+        assert!(LessonReadOptions::from_command("/view best <x").is_none());
+        assert_eq!(
+            LessonReadOptions::from_command("/view best+carousel <35").unwrap(),
+            LessonReadOptions {
+                backward: true,
+                ..LessonReadOptions::new(LessonStatusRange::Best, Some(35)).carousel(true)
+            }
+        );
+        assert_eq!(
+            LessonReadOptions::from_command("/random").unwrap(),
+            LessonReadOptions::new(LessonStatusRange::Approved, None).mode(ReadMode::Random)
+        );
+        assert_eq!(
+            LessonReadOptions::from_command("/daily").unwrap(),
+            LessonReadOptions::new(LessonStatusRange::Approved, None).mode(ReadMode::Daily)
+        );
+        assert_eq!(
+            LessonReadOptions::from_command("/view approved+own+random 7").unwrap(),
+            LessonReadOptions::new(LessonStatusRange::Approved, Some(7))
+                .own_lang(true)
+                .mode(ReadMode::Random)
+        );
+        assert_eq!(
+            LessonReadOptions::from_command("/view new+oldest").unwrap(),
+            LessonReadOptions::new(LessonStatusRange::New, None).sort(SortOrder::Oldest)
+        );
+        assert_eq!(
+            LessonReadOptions::from_command("/view bocS <12").unwrap(),
+            LessonReadOptions {
+                backward: true,
+                ..LessonReadOptions::new(LessonStatusRange::Best, Some(12))
+                    .own_lang(true)
+                    .carousel(true)
+                    .sort(SortOrder::Score)
+            }
+        );
+        assert!(LessonReadOptions::from_command("/view bq").is_none());
+        let random = LessonReadOptions::from_command("/view aR 7 5 3").unwrap();
+        assert_eq!(random.recent, [Some(5), Some(3)]);
+        assert_eq!(random.to_command(), "/view aR 7 5 3");
+        assert_eq!(random.after(9).to_command(), "/view aR 9 7 5");
+        assert_eq!(random.after(7), random);
+        assert!(LessonReadOptions::from_command("/view aR 7 x").is_none());
+        assert_eq!(
+            LessonReadOptions::from_command("/view a 7")
+                .unwrap()
+                .after(8),
+            LessonReadOptions::new(LessonStatusRange::Approved, Some(8))
+        );
+        assert_eq!(
+            LessonReadOptions::from_command("/saved").unwrap(),
+            LessonReadOptions::new(LessonStatusRange::Approved, None).saved(true)
+        );
+        assert_eq!(
+            LessonReadOptions::from_command("/view as 3").unwrap(),
+            LessonReadOptions::from_command("/view approved+saved 3").unwrap()
+        );
     }
 
     #[test]
     fn lesson_read_options_to_command() {
-        assert_eq!(LessonReadOptions::default().to_command(), "/view approved");
+        assert_eq!(LessonReadOptions::default().to_command(), "/view a");
         assert_eq!(
             LessonReadOptions::new(LessonStatusRange::Best, Some(35)).to_command(),
-            "/view best 35"
+            "/view b 35"
+        );
+        assert_eq!(
+            LessonReadOptions::new(LessonStatusRange::Approved, None)
+                .own_lang(true)
+                .to_command(),
+            "/view ao"
+        );
+        assert_eq!(
+            LessonReadOptions::new(LessonStatusRange::Approved, Some(7))
+                .mode(ReadMode::Random)
+                .to_command(),
+            "/view aR 7"
+        );
+        assert_eq!(
+            LessonReadOptions {
+                backward: true,
+                ..LessonReadOptions::new(LessonStatusRange::All, Some(7))
+                    .own_lang(true)
+                    .carousel(true)
+            }
+            .to_command(),
+            "/view xoc <7"
+        );
+        let view = LessonReadOptions {
+            backward: true,
+            ..LessonReadOptions::new(LessonStatusRange::Approved, Some(7))
+                .own_lang(true)
+                .saved(true)
+                .unread(true)
+        };
+        let sorted = view.sorted_by(SortOrder::Score);
+        assert_eq!(sorted.to_command(), "/view aosuS");
+        assert_eq!(
+            LessonReadOptions::from_command(&sorted.to_command()),
+            Some(
+                LessonReadOptions::new(LessonStatusRange::Approved, None)
+                    .own_lang(true)
+                    .saved(true)
+                    .unread(true)
+                    .sort(SortOrder::Score)
+            )
+        );
+    }
+
+    #[test]
+    fn lesson_status_transitions() {
+        assert!(LessonStatus::New.can_become(LessonStatus::NeedsEdit));
+        assert!(LessonStatus::Duplicate.can_become(LessonStatus::Approved));
+        assert!(!LessonStatus::Best.can_become(LessonStatus::Rejected));
+        assert!(!LessonStatus::New.can_become(LessonStatus::New));
+        for status in [LessonStatus::Superseded, LessonStatus::Withdrawn] {
+            assert!(status.transitions().is_empty(), "{status:?}");
+        }
+        assert!(!LessonStatus::sources(LessonStatus::Superseded).contains(&LessonStatus::Best));
+        assert_eq!(
+            LessonStatus::sources(LessonStatus::Archived),
+            [LessonStatus::Approved, LessonStatus::Best]
+        );
+    }
+
+    #[test]
+    fn callback_data_fits_telegram_limit() {
+        let view = LessonReadOptions {
+            backward: true,
+            recent: [Some(i32::MIN); RECENT_LESSONS],
+            ..LessonReadOptions::new(LessonStatusRange::Rejected, Some(i32::MIN))
+                .own_lang(true)
+                .carousel(true)
+                .mode(ReadMode::Random)
+                .sort(SortOrder::Oldest)
+                .unread(true)
+        };
+        for data in [
+            view.to_command(),
+            SetLessonStatus::new(view, i32::MIN, LessonStatus::Rejected)
+                .reason(&"x".repeat(MAX_REJECTION_REASON_LEN))
+                .prev_status(LessonStatus::NeedsEdit)
+                .to_command(),
+            RejectionReasons {
+                view,
+                lesson_id: i32::MIN,
+                prev_status: LessonStatus::NeedsEdit,
+                show: false,
+            }
+            .to_command(),
+            ToggleTranslation::new(view, i32::MIN, false).to_command(),
+            SaveLesson::new(view.saved(true), i32::MIN, false)
+                .original(true)
+                .to_command(),
+        ] {
+            assert!(data.len() <= 64, "{data}");
+        }
+        assert_eq!(
+            LessonReadOptions::from_view(&view.view()),
+            Some(LessonReadOptions {
+                prev_lesson: None,
+                backward: false,
+                recent: [None; RECENT_LESSONS],
+                ..view
+            })
+        );
+        assert_eq!(
+            LessonReadOptions::from_command(&view.to_command()),
+            Some(view)
+        );
+    }
+
+    #[test]
+    fn show_lesson_from_command() {
+        assert!(ShowLesson::from_command("/lesson").is_none());
+        assert!(ShowLesson::from_command("/lesson x").is_none());
+        assert!(ShowLesson::from_command("/start").is_none());
+        assert!(ShowLesson::from_command("/start 12").is_none());
+        assert!(ShowLesson::from_command("/view 12").is_none());
+        assert_eq!(
+            ShowLesson::from_command("/lesson 12"),
+            Some(ShowLesson { lesson_id: 12 })
+        );
+        assert_eq!(
+            ShowLesson::from_command("/start lesson_12"),
+            Some(ShowLesson { lesson_id: 12 })
+        );
+        assert_eq!(
+            deep_link("war_lessons_bot", 12),
+            "https://t.me/war_lessons_bot?start=lesson_12"
+        );
+    }
+
+    #[test]
+    fn strip_bot_mention_from_command() {
+        let strip = |cmd| strip_mention(cmd, "war_lessons_bot");
+        assert_eq!(strip("/approve@War_Lessons_Bot 12 13"), "/approve 12 13");
+        assert_eq!(strip("/batch@war_lessons_bot"), "/batch");
+        assert_eq!(strip("/approve 12"), "/approve 12");
+        assert_eq!(strip("/approve@other_bot 12"), "/approve@other_bot 12");
+        assert_eq!(
+            strip("/reject spam@war_lessons_bot 1"),
+            "/reject spam@war_lessons_bot 1"
+        );
+    }
+
+    #[test]
+    fn translate_lesson_from_command() {
+        assert!(TranslateLesson::from_command("/translate").is_none());
+        assert!(TranslateLesson::from_command("/translate x").is_none());
+        assert!(TranslateLesson::from_command("/translate 1 xx text").is_none());
+        assert_eq!(
+            TranslateLesson::from_command("/translate 1").unwrap(),
+            TranslateLesson {
+                lesson_id: 1,
+                translation: None
+            }
+        );
+        assert_eq!(
+            TranslateLesson::from_command("/translate 1 en\nLine 1\nLine 2").unwrap(),
+            TranslateLesson {
+                lesson_id: 1,
+                translation: Some((Lang::from("en"), "Line 1\nLine 2".into()))
+            }
+        );
+    }
+
+    #[test]
+    fn toggle_translation_command() {
+        assert!(ToggleTranslation::from_command("/lesson-text best 1").is_none());
+        assert!(ToggleTranslation::from_command("/lesson-text best 1 foo").is_none());
+        let toggle = ToggleTranslation::new(
+            LessonReadOptions::new(LessonStatusRange::Best, None),
+            1,
+            true,
+        );
+        assert_eq!(toggle.to_command(), "/lesson-text b 1 original");
+        assert_eq!(
+            ToggleTranslation::from_command(&toggle.to_command()).unwrap(),
+            toggle
         );
     }
 
     #[test]
-    fn set_lesson_status_from_command() {
-        assert!(SetLessonStatus::from_command("/unknown").is_none());
-        assert!(SetLessonStatus::from_command("/set-lesson-status").is_none());
-        assert!(SetLessonStatus::from_command("/set-lesson-status foo bar").is_none());
-        assert!(SetLessonStatus::from_command("/set-lesson-status 1 bar").is_none());
-        assert!(SetLessonStatus::from_command("/set-lesson-status foo best").is_none());
+    fn save_lesson_command() {
+        assert!(SaveLesson::from_command("/save").is_none());
+        assert!(SaveLesson::from_command("/save a").is_none());
+        assert!(SaveLesson::from_command("/saved a 1").is_none());
+        let save = SaveLesson::new(LessonReadOptions::default(), 1, true);
+        assert_eq!(save.to_command(), "/save a 1");
+        assert_eq!(SaveLesson::from_command("/save a 1").unwrap(), save);
         assert_eq!(
-            SetLessonStatus::from_command("/set-lesson-status approved 1 best").unwrap(),
-            SetLessonStatus::new(LessonStatusRange::Approved, 1, LessonStatus::Best)
+            SaveLesson::from_command("/unsave as 1").unwrap(),
+            SaveLesson::new(LessonReadOptions::default().saved(true), 1, false)
         );
+        assert!(SaveLesson::from_command("/save a 1 translation").is_none());
+        let save =
+            SaveLesson::new(LessonReadOptions::default().carousel(true), 1, true).original(true);
+        assert_eq!(save.to_command(), "/save ac 1 original");
+        assert_eq!(SaveLesson::from_command(&save.to_command()).unwrap(), save);
     }
 }
@@ -1,21 +1,62 @@
 mod add;
+mod batch;
+mod claim;
 mod config;
+mod detect;
+mod diff;
+mod edit;
 mod error;
+mod html;
+mod inline;
 mod lesson;
+mod lineage;
+mod moderation;
+mod note;
+mod read_state;
+mod reader;
 mod replier;
+mod report;
+mod retention;
 mod spam_token;
+mod subscription;
 mod text;
+mod text_override;
+mod translation;
+mod trash;
+mod util;
 
 pub use add::add_lesson;
+pub use batch::BatchCommand;
+pub use claim::ReleaseClaims;
 pub use config::CONF;
+pub use detect::{backfill_lesson_langs, detect_lang, UNDETERMINED_LANG};
+pub use edit::EditLesson;
 pub use error::{eprint_error, internal_error, log_error, Error, Result};
-pub use lesson::{LessonReadOptions, LessonStatusRange, SetLessonStatus};
+pub use html::HtmlError;
+pub use inline::answer_inline_query;
+pub use lesson::{
+    set_bot_username, strip_bot_mention, LessonReadOptions, LessonStatusRange, ReadMode,
+    SaveLesson, ShowLesson, SortOrder, ToggleTranslation, TranslateLesson, NOOP_CALLBACK,
+};
+pub use lineage::{MergeLessons, SplitLesson};
+pub use moderation::{RejectionReasons, SetLessonStatus};
+pub use note::NoteCommand;
+pub use read_state::{purge_read_state, ReadStateCommand};
+pub use reader::reader_key;
 pub use replier::{Replier, Reply, ReplyResult};
+pub use report::RejectionReport;
+pub use retention::{apply_retention, RetentionPolicy, RetentionReport};
 pub use spam_token::SpamTokenGenerator;
+pub use subscription::{notify_subscribers, SubscriptionCommand};
 use teloxide::types::{
     InlineKeyboardButton, InlineKeyboardMarkup, MediaKind, MediaText, Message, MessageKind,
 };
-pub use text::{Lang, Translate, Translations, TEXT};
+pub use text::{
+    Lang, Languages, TextError, Translate, Translations, DEFAULT_LANG, HTML_TEXTS, LANGS, TEXT,
+};
+pub use text_override::{load_text_overrides, refresh_text_overrides, TextCommand};
+pub use translation::{Catalog, Entry, Format, ImportReport};
+pub use trash::{purge_trash, TrashCommand};
 
 pub fn init_logging() -> Result<()> {
     if CONF.journal_logging {
@@ -31,27 +72,56 @@ pub async fn start_keyboard(
     pool: &sqlx::PgPool,
     lang: Lang,
     is_moderator: bool,
+    unread: bool,
 ) -> InlineKeyboardMarkup {
+    let read = |range| LessonReadOptions::new(range, None).unread(unread);
     let mut lines = vec![vec![
         InlineKeyboardButton::callback(
             TEXT.read_best.to(lang),
-            LessonReadOptions::new(LessonStatusRange::Best, None).to_command(),
+            read(LessonStatusRange::Best).to_command(),
         ),
         InlineKeyboardButton::callback(
             TEXT.read_approved.to(lang),
-            LessonReadOptions::new(LessonStatusRange::Approved, None).to_command(),
+            read(LessonStatusRange::Approved).to_command(),
         ),
         InlineKeyboardButton::callback(
             TEXT.read_all.to(lang),
-            LessonReadOptions::new(LessonStatusRange::All, None).to_command(),
+            read(LessonStatusRange::All).to_command(),
         ),
     ]];
+    lines.push(vec![
+        InlineKeyboardButton::callback(
+            TEXT.random_lesson.to(lang),
+            read(LessonStatusRange::Approved)
+                .mode(ReadMode::Random)
+                .to_command(),
+        ),
+        InlineKeyboardButton::callback(
+            TEXT.lesson_of_the_day.to(lang),
+            LessonReadOptions::new(LessonStatusRange::Approved, None)
+                .mode(ReadMode::Daily)
+                .to_command(),
+        ),
+        InlineKeyboardButton::callback(
+            TEXT.browse_lessons.to(lang),
+            read(LessonStatusRange::Approved)
+                .carousel(true)
+                .to_command(),
+        ),
+    ]);
+    lines.push(vec![if unread {
+        InlineKeyboardButton::callback(TEXT.include_read.to(lang), "/unread off")
+    } else {
+        InlineKeyboardButton::callback(TEXT.unread_only.to(lang), "/unread on")
+    }]);
     if is_moderator {
         let (new, rejected) = sqlx::query!(
             r#"
             SELECT
-                (SELECT count(*) FROM lesson WHERE status = 'new') as "new!",
-                (SELECT count(*) FROM lesson WHERE status = 'rejected') as "rejected!"
+                (SELECT count(*) FROM lesson
+                 WHERE status IN ('new', 'needs_edit') AND deleted_at IS NULL) as "new!",
+                (SELECT count(*) FROM lesson
+                 WHERE status IN ('rejected', 'duplicate') AND deleted_at IS NULL) as "rejected!"
             "#
         )
         .map(|r| (r.new, r.rejected))
@@ -61,16 +131,25 @@ pub async fn start_keyboard(
         lines.push(vec![
             InlineKeyboardButton::callback(
                 format!("Moderate New ({new})"),
-                LessonReadOptions::new(LessonStatusRange::New, None).to_command(),
+                LessonReadOptions::new(LessonStatusRange::New, None)
+                    .sort(SortOrder::Oldest)
+                    .to_command(),
             ),
             InlineKeyboardButton::callback(
                 format!("Moderate Rejected ({rejected})"),
                 LessonReadOptions::new(LessonStatusRange::Rejected, None).to_command(),
             ),
+            InlineKeyboardButton::callback("📋 Batch", "/batch"),
         ])
     };
     lines.push(vec![
         InlineKeyboardButton::callback(TEXT.add_lesson.to(lang), "/add"),
+        InlineKeyboardButton::callback(
+            TEXT.saved_lessons.to(lang),
+            LessonReadOptions::new(LessonStatusRange::Approved, None)
+                .saved(true)
+                .to_command(),
+        ),
         InlineKeyboardButton::callback(TEXT.help.to(lang), "/help"),
     ]);
     InlineKeyboardMarkup::new(lines)
@@ -0,0 +1,385 @@
+use crate::{
+    detect_lang, internal_error,
+    lesson::{Lesson, LessonStatus},
+    moderation::post_to_moderators,
+    util::{join_ids, truncate},
+    Error, Replier, ReplyResult, TEXT,
+};
+use sqlx::{query, PgPool};
+use std::fmt::Write;
+use teloxide::{
+    payloads::SendMessageSetters,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+const SPLIT_CMD: &str = "/split";
+const MERGE_CMD: &str = "/merge";
+/// A split chooses among the boundaries after the first paragraphs only
+const MAX_SPLIT_CUTS: usize = 32;
+const MAX_MERGED_LESSONS: usize = 10;
+const PARAGRAPH_PREVIEW_LEN: usize = 100;
+
+/// Splits a lesson into several at the paragraph boundaries a moderator picks
+#[derive(Debug, PartialEq, Eq)]
+pub struct SplitLesson {
+    pub(crate) lesson_id: i32,
+    /// Bit `i` cuts the lesson after the paragraph `i`
+    pub(crate) cuts: u32,
+    /// Split now or show the boundaries to pick
+    pub(crate) apply: bool,
+}
+
+/// Merges lessons into a new one in the given order
+#[derive(Debug, PartialEq, Eq)]
+pub struct MergeLessons {
+    lesson_ids: Vec<i32>,
+}
+
+impl SplitLesson {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let mut parts = cmd.split_whitespace();
+        if parts.next()? != SPLIT_CMD {
+            return None;
+        }
+        let lesson_id = parts.next()?.parse().ok()?;
+        let (cuts, apply) = match (parts.next(), parts.next(), parts.next()) {
+            (None, None, None) => (0, false),
+            (Some(cuts), None, None) => (u32::from_str_radix(cuts, 16).ok()?, false),
+            (Some(cuts), Some("apply"), None) => (u32::from_str_radix(cuts, 16).ok()?, true),
+            _ => return None,
+        };
+        Some(Self {
+            lesson_id,
+            cuts,
+            apply,
+        })
+    }
+
+    pub(crate) fn to_command(&self) -> String {
+        let apply = if self.apply { " apply" } else { "" };
+        format!("{SPLIT_CMD} {} {:x}{apply}", self.lesson_id, self.cuts)
+    }
+
+    fn is_cut(&self, boundary: usize) -> bool {
+        self.cuts & (1 << boundary) != 0
+    }
+
+    /// Joins the paragraphs between the cuts
+    fn parts(&self, paragraphs: &[String]) -> Vec<String> {
+        let mut parts = vec![];
+        let mut part = vec![];
+        for (i, paragraph) in paragraphs.iter().enumerate() {
+            part.push(paragraph.as_str());
+            if i < MAX_SPLIT_CUTS && self.is_cut(i) {
+                parts.push(part.join("\n\n"));
+                part.clear();
+            }
+        }
+        if !part.is_empty() {
+            parts.push(part.join("\n\n"));
+        }
+        parts
+    }
+
+    /// Shows the paragraphs with a toggle for each boundary, the first time in a new
+    /// message to keep the lesson one
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let lesson = match Lesson::find(pool, self.lesson_id, repl.lang, None).await {
+            Ok(Some(lesson)) => lesson,
+            Ok(None) => {
+                repl.send_text(&TEXT.lesson_not_found).await?;
+                return Ok(());
+            }
+            Err(e) => {
+                repl.send_text(internal_error(&e)).await?;
+                return Ok(());
+            }
+        };
+        let paragraphs = paragraphs(&lesson.text);
+        if !lesson.status.can_become(LessonStatus::Superseded) {
+            let status = lesson.status.as_ref();
+            repl.send_text(format!(
+                "Lesson {} can't be split while {status}",
+                lesson.id
+            ))
+            .await?;
+            return Ok(());
+        } else if paragraphs.len() < 2 {
+            repl.send_text(format!("Lesson {} has a single paragraph", lesson.id))
+                .await?;
+            return Ok(());
+        }
+        let parts = self.parts(&paragraphs);
+        if self.apply && parts.len() > 1 {
+            let res = Lesson::supersede(pool, &[lesson.id], |_| parts, repl.sender_id()).await;
+            let text = match res {
+                Ok(Some(ids)) => {
+                    post_results(pool, repl, &ids).await;
+                    format!("✂️ Lesson {} split into {}", lesson.id, join_ids(&ids))
+                }
+                Ok(None) => format!("Lesson {} can't be split anymore", lesson.id),
+                Err(e) => internal_error(&e).to(repl.lang),
+            };
+            reply_outcome(repl, text).await?;
+            return Ok(());
+        }
+        let mut text = format!("Choose where to split lesson {}:\n", lesson.id);
+        for (i, paragraph) in paragraphs.iter().enumerate() {
+            write!(
+                text,
+                "\n{}. {}",
+                i + 1,
+                truncate(paragraph, PARAGRAPH_PREVIEW_LEN)
+            )
+            .ok();
+        }
+        let boundaries = (0..paragraphs.len() - 1).take(MAX_SPLIT_CUTS).map(|i| {
+            let cut = Self {
+                cuts: self.cuts ^ (1 << i),
+                ..*self
+            };
+            let mark = if self.is_cut(i) { "✂️" } else { "|" };
+            InlineKeyboardButton::callback(format!("{} {mark} {}", i + 1, i + 2), cut.to_command())
+        });
+        let mut lines = boundaries
+            .collect::<Vec<_>>()
+            .chunks(4)
+            .map(<[_]>::to_vec)
+            .collect::<Vec<_>>();
+        lines.push(vec![InlineKeyboardButton::callback(
+            format!("✅ Split into {}", parts.len()),
+            Self {
+                apply: true,
+                ..*self
+            }
+            .to_command(),
+        )]);
+        let keyboard = InlineKeyboardMarkup::new(lines);
+        if repl.callback && (self.cuts != 0 || self.apply) {
+            repl.edit_or_send(text, keyboard).await?;
+        } else {
+            repl.send_text(text).reply_markup(keyboard).await?;
+        }
+        Ok(())
+    }
+}
+
+impl MergeLessons {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let mut parts = cmd.split_whitespace();
+        if parts.next()? != MERGE_CMD {
+            return None;
+        }
+        let mut lesson_ids = vec![];
+        for id in parts {
+            let id = id.parse().ok()?;
+            if lesson_ids.contains(&id) {
+                return None;
+            }
+            lesson_ids.push(id);
+        }
+        (2..=MAX_MERGED_LESSONS)
+            .contains(&lesson_ids.len())
+            .then_some(Self { lesson_ids })
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let merge = |texts: Vec<String>| vec![texts.join("\n\n")];
+        let ids = join_ids(&self.lesson_ids);
+        let text = match Lesson::supersede(pool, &self.lesson_ids, merge, repl.sender_id()).await {
+            Ok(Some(merged)) => {
+                post_results(pool, repl, &merged).await;
+                format!("🔗 Lessons {ids} merged into {}", join_ids(&merged))
+            }
+            Ok(None) => {
+                format!("Lessons {ids} must exist and be new, needing edit, approved or rejected")
+            }
+            Err(e) => internal_error(&e).to(repl.lang),
+        };
+        reply_outcome(repl, text).await
+    }
+}
+
+/// The new lessons wait for moderation like added ones
+async fn post_results(pool: &PgPool, repl: &Replier, lesson_ids: &[i32]) {
+    for &lesson_id in lesson_ids {
+        post_to_moderators(pool, &repl.bot, lesson_id).await;
+    }
+}
+
+/// Replaces the pressed keyboard message with the outcome, a command gets a new message
+async fn reply_outcome(repl: &Replier, text: String) -> ReplyResult {
+    if repl.callback {
+        repl.edit_or_send(text, InlineKeyboardMarkup::default())
+            .await?;
+    } else {
+        repl.send_text(text).await?;
+    }
+    Ok(())
+}
+
+/// Paragraphs are separated by blank lines, a text without them is split by lines
+fn paragraphs(text: &str) -> Vec<String> {
+    let mut paragraphs = vec![];
+    let mut paragraph = vec![];
+    for line in text.lines().map(str::trim_end) {
+        if line.trim().is_empty() {
+            if !paragraph.is_empty() {
+                paragraphs.push(paragraph.join("\n"));
+                paragraph.clear();
+            }
+        } else {
+            paragraph.push(line);
+        }
+    }
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph.join("\n"));
+    }
+    if paragraphs.len() == 1 {
+        paragraphs = text
+            .lines()
+            .map(str::trim_end)
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_owned)
+            .collect();
+    }
+    paragraphs
+}
+
+impl Lesson {
+    /// Replaces the lessons with new ones made of their texts in the `lesson_ids` order.
+    /// The new lessons are moderated again and have no spam token, they aren't new posts
+    /// of the author to limit.
+    /// Returns `None` if a lesson is missing or can't be superseded.
+    async fn supersede(
+        pool: &PgPool,
+        lesson_ids: &[i32],
+        texts: impl FnOnce(Vec<String>) -> Vec<String>,
+        moderator: Option<i64>,
+    ) -> Result<Option<Vec<i32>>, Error> {
+        let res = async {
+            let mut tx = pool.begin().await?;
+            let sources = query!(
+                r#"
+                UPDATE lesson
+                SET status = 'superseded', claimed_by = NULL, claimed_at = NULL
+                WHERE id = ANY($1) AND status = ANY($2) AND deleted_at IS NULL
+                RETURNING id, text, created_at
+                "#,
+                lesson_ids,
+                LessonStatus::sources(LessonStatus::Superseded) as Vec<LessonStatus>,
+            )
+            .fetch_all(&mut tx)
+            .await?;
+            if sources.len() != lesson_ids.len() {
+                return Ok(None);
+            }
+            let mut ordered = vec![];
+            for id in lesson_ids {
+                ordered.extend(sources.iter().find(|s| s.id == *id));
+            }
+            let created_at = ordered.iter().map(|s| s.created_at).min();
+            let texts = texts(ordered.iter().map(|s| s.text.clone()).collect());
+            let mut result_ids = vec![];
+            for text in &texts {
+                let id = query!(
+                    r#"
+                    INSERT INTO lesson (text, created_at, lang)
+                    VALUES ($1, $2, $3)
+                    RETURNING id
+                    "#,
+                    text,
+                    created_at,
+                    detect_lang(text),
+                )
+                .fetch_one(&mut tx)
+                .await?
+                .id;
+                result_ids.push(id);
+            }
+            query!(
+                r#"
+                INSERT INTO lesson_lineage (source_id, result_id)
+                SELECT s, r FROM unnest($1::int[]) s, unnest($2::int[]) r
+                "#,
+                lesson_ids,
+                &result_ids,
+            )
+            .execute(&mut tx)
+            .await?;
+            query!(
+                r#"
+                INSERT INTO lesson_status_change (lesson_id, status, moderator)
+                SELECT id, 'superseded', $2 FROM unnest($1::int[]) id
+                "#,
+                lesson_ids,
+                moderator,
+            )
+            .execute(&mut tx)
+            .await?;
+            tx.commit().await?;
+            Ok(Some(result_ids))
+        };
+        res.await
+            .map_err(|e| Error::SupersedeLessons(e, lesson_ids.to_vec()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_lesson_command() {
+        assert!(SplitLesson::from_command("/split").is_none());
+        assert!(SplitLesson::from_command("/split 1 zz").is_none());
+        assert!(SplitLesson::from_command("/split 1 5 now").is_none());
+        let cmd = SplitLesson {
+            lesson_id: 1,
+            cuts: 0b101,
+            apply: true,
+        };
+        assert_eq!(cmd.to_command(), "/split 1 5 apply");
+        assert_eq!(SplitLesson::from_command(&cmd.to_command()), Some(cmd));
+        assert_eq!(
+            SplitLesson::from_command("/split 7"),
+            Some(SplitLesson {
+                lesson_id: 7,
+                cuts: 0,
+                apply: false
+            })
+        );
+    }
+
+    #[test]
+    fn split_lesson_parts() {
+        let text = "Tip one\ncontinued\n\n \nTip two\n\nTip three";
+        assert_eq!(super::paragraphs("one\ntwo"), ["one", "two"]);
+        let paragraphs = super::paragraphs(text);
+        assert_eq!(paragraphs, ["Tip one\ncontinued", "Tip two", "Tip three"]);
+        let split = |cuts| {
+            SplitLesson {
+                lesson_id: 1,
+                cuts,
+                apply: true,
+            }
+            .parts(&paragraphs)
+        };
+        assert_eq!(split(0), ["Tip one\ncontinued\n\nTip two\n\nTip three"]);
+        assert_eq!(split(0b10), ["Tip one\ncontinued\n\nTip two", "Tip three"]);
+        assert_eq!(split(0b11), ["Tip one\ncontinued", "Tip two", "Tip three"]);
+    }
+
+    #[test]
+    fn merge_lessons_from_command() {
+        assert_eq!(
+            MergeLessons::from_command("/merge 3 1 2"),
+            Some(MergeLessons {
+                lesson_ids: vec![3, 1, 2]
+            })
+        );
+        assert!(MergeLessons::from_command("/merge 3").is_none());
+        assert!(MergeLessons::from_command("/merge 3 3").is_none());
+        assert!(MergeLessons::from_command("/merge 3 x").is_none());
+    }
+}
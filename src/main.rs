@@ -1,38 +1,51 @@
 use sqlx::{migrate, PgPool};
-use std::sync::{Arc, Mutex};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
 use teloxide::prelude::*;
 use war_lessons_bot::{
-    add_lesson, eprint_error, init_logging, log_error, message_text, start_keyboard, Error,
-    LessonReadOptions, Replier, ReplyResult, Result, SetLessonStatus, SpamTokenGenerator, CONF,
-    TEXT,
+    add_lesson, answer_inline_query, apply_retention, eprint_error, init_logging,
+    load_text_overrides, log_error, message_text, notify_subscribers, purge_read_state,
+    purge_trash, refresh_text_overrides, set_bot_username, start_keyboard, strip_bot_mention,
+    BatchCommand, EditLesson, Error, LessonReadOptions, MergeLessons, NoteCommand,
+    ReadStateCommand, RejectionReasons, RejectionReport, ReleaseClaims, Replier, ReplyResult,
+    Result, RetentionReport, SaveLesson, SetLessonStatus, ShowLesson, SpamTokenGenerator,
+    SplitLesson, SubscriptionCommand, TextCommand, ToggleTranslation, TranslateLesson,
+    TrashCommand, CONF, LANGS, NOOP_CALLBACK, TEXT,
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    init_logging().map_err(|e| {
-        eprint_error(&e);
-        e
-    })?;
-    run().await.map_err(|e| {
-        log_error(&e);
-        e
-    })
+    init_logging().inspect_err(eprint_error)?;
+    run().await.inspect_err(log_error)
 }
 
 async fn run() -> Result<()> {
+    LANGS.report_incomplete();
+
     let pool = PgPool::connect(&CONF.database_url)
         .await
         .map_err(Error::CreatePgPool)?;
     migrate!().run(&pool).await.map_err(Error::Migrate)?;
+    load_text_overrides(&pool).await?;
+    tokio::spawn(refresh_text_overrides(pool.clone()));
+    tokio::spawn(purge_read_state(pool.clone()));
+    tokio::spawn(purge_trash(pool.clone()));
+    tokio::spawn(apply_retention(pool.clone()));
 
     let bot = Bot::new(&CONF.teloxide_token).auto_send();
+    let me = bot.get_me().await.map_err(Error::GetMe)?;
+    set_bot_username(me.username());
+    tokio::spawn(notify_subscribers(bot.clone(), pool.clone()));
     let spam_gen = Arc::new(Mutex::new(SpamTokenGenerator::new(
         CONF.spam_token_lifetime,
     )));
 
     let handler = dptree::entry()
         .branch(Update::filter_message().endpoint(message_handler))
-        .branch(Update::filter_callback_query().endpoint(callback_handler));
+        .branch(Update::filter_callback_query().endpoint(callback_handler))
+        .branch(Update::filter_inline_query().endpoint(inline_query_handler));
 
     Dispatcher::builder(bot, handler)
         .dependencies(dptree::deps![pool, spam_gen])
@@ -51,8 +64,30 @@ async fn message_handler(
 ) -> ReplyResult {
     let repl = Replier::from_message(bot, &message);
     if let Some(text) = message_text(&message) {
-        if text.starts_with('/') {
+        let quick_status =
+            SetLessonStatus::from_reply(&message).filter(|_| Role::Moderator.allows(&repl));
+        if let (Some(cmd), Some(lesson)) = (quick_status, message.reply_to_message()) {
+            // The lesson message the moderator replied to shows the new status
+            let lesson_repl = Replier {
+                message_id: lesson.id,
+                ..repl.clone()
+            };
+            let notice = cmd.reply(&pool, &lesson_repl).await?;
+            if !notice.is_empty() {
+                repl.send_text(notice).await?;
+            }
+        } else if text.starts_with('/') {
             handle_command(&pool, &repl, text).await?;
+        } else if let Some(cmd) =
+            EditLesson::from_reply(&message).filter(|_| Role::Moderator.allows(&repl))
+        {
+            cmd.reply(&pool, &repl).await?;
+        } else if let Some(cmd) =
+            NoteCommand::from_reply(&message).filter(|_| Role::Moderator.allows(&repl))
+        {
+            cmd.reply(&pool, &repl).await?;
+        } else if repl.in_moderators_chat() {
+            // Moderators talk to each other there
         } else if let Some(user_id) = repl.user_id() {
             let spam_token = spam_gen.lock().expect("spam_gen.lock").generate(user_id);
             add_lesson(&pool, &repl, &spam_token, text).await?;
@@ -68,21 +103,36 @@ async fn message_handler(
 
 async fn callback_handler(q: CallbackQuery, bot: AutoSend<Bot>, pool: PgPool) -> ReplyResult {
     if let (Some(cmd), Some(repl)) = (&q.data, Replier::from_callback_query(bot, &q)) {
-        if let Some(opts) = SetLessonStatus::from_command(&cmd) {
-            if repl.is_moderator() {
-                opts.reply(&pool, &repl).await?;
+        if let Some(opts) = SetLessonStatus::from_command(cmd) {
+            if Role::Moderator.allows(&repl) {
+                let text = opts.reply(&pool, &repl).await?;
+                repl.bot.answer_callback_query(q.id).text(text).await?;
+            } else {
                 repl.bot
                     .answer_callback_query(q.id)
-                    .text("Lesson status updated")
+                    .text("Forbidden")
                     .await?;
+            }
+        } else if let Some(cmd) = RejectionReasons::from_command(cmd) {
+            if Role::Moderator.allows(&repl) {
+                cmd.reply(&pool, &repl).await?;
+                repl.bot.answer_callback_query(q.id).await?;
             } else {
                 repl.bot
                     .answer_callback_query(q.id)
                     .text("Forbidden")
                     .await?;
             }
+        } else if let Some(cmd) = SaveLesson::from_command(cmd) {
+            cmd.reply(&pool, &repl).await?;
+            repl.bot.answer_callback_query(q.id).await?;
+        } else if let Some(opts) = ToggleTranslation::from_command(cmd) {
+            opts.reply(&pool, &repl).await?;
+            repl.bot.answer_callback_query(q.id).await?;
+        } else if cmd == NOOP_CALLBACK {
+            repl.bot.answer_callback_query(q.id).await?;
         } else if cmd.starts_with('/') {
-            handle_command(&pool, &repl, &cmd).await?;
+            handle_command(&pool, &repl, cmd).await?;
             repl.bot.answer_callback_query(q.id).await?;
         } else {
             repl.bot
@@ -94,17 +144,77 @@ async fn callback_handler(q: CallbackQuery, bot: AutoSend<Bot>, pool: PgPool) ->
     Ok(())
 }
 
+async fn inline_query_handler(q: InlineQuery, bot: AutoSend<Bot>, pool: PgPool) -> ReplyResult {
+    answer_inline_query(&bot, &pool, &q).await
+}
+
 async fn handle_command(pool: &PgPool, repl: &Replier, text: &str) -> ReplyResult {
-    if text == "/start" || text == "/help" {
+    // Groups address the bot as `/approve@bot_username`
+    let text = &strip_bot_mention(text);
+    if let Some(cmd) = ShowLesson::from_command(text) {
+        cmd.reply(pool, repl).await?;
+    } else if text == "/start" || text.starts_with("/start ") || text == "/help" {
         repl.send_html(&TEXT.help_message)
-            .reply_markup(start_keyboard(pool, repl.lang, repl.is_moderator()).await)
+            .reply_markup(start_keyboard(pool, repl.lang, repl.is_moderator(), false).await)
             .await?;
     } else if text == "/add" {
         repl.send_text(&TEXT.add_lesson_message).await?;
     } else if let Some(opts) = LessonReadOptions::from_command(text) {
         opts.reply(pool, repl).await?;
+    } else if let Some(cmd) = SubscriptionCommand::from_command(text) {
+        cmd.reply(pool, repl).await?;
+    } else if let Some(cmd) = ReadStateCommand::from_command(text) {
+        cmd.reply(pool, repl).await?;
+    } else if let Some(cmd) = NoteCommand::from_command(text) {
+        Role::Moderator.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = RejectionReport::from_command(text) {
+        Role::Moderator.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = ReleaseClaims::from_command(text) {
+        Role::Moderator.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = EditLesson::from_command(text) {
+        Role::Moderator.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = BatchCommand::from_command(text) {
+        Role::Moderator.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = SplitLesson::from_command(text) {
+        Role::Moderator.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = MergeLessons::from_command(text) {
+        Role::Moderator.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = TranslateLesson::from_command(text) {
+        Role::Moderator.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = TrashCommand::from_command(text) {
+        Role::Admin.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = RetentionReport::from_command(text) {
+        Role::Admin.guard(repl, cmd.reply(pool, repl)).await?;
+    } else if let Some(cmd) = TextCommand::from_command(text) {
+        Role::Admin.guard(repl, cmd.reply(pool, repl)).await?;
     } else {
         repl.send_text(&TEXT.unknown_command).await?;
     };
     Ok(())
 }
+
+/// Who may run a command
+#[derive(Clone, Copy)]
+enum Role {
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    fn allows(self, repl: &Replier) -> bool {
+        match self {
+            Self::Moderator => repl.is_moderator(),
+            Self::Admin => repl.is_admin(),
+        }
+    }
+
+    /// Runs the command `reply` if the user has the role, answers "Forbidden" otherwise
+    async fn guard(self, repl: &Replier, reply: impl Future<Output = ReplyResult>) -> ReplyResult {
+        if self.allows(repl) {
+            reply.await
+        } else {
+            repl.send_text("Forbidden").await?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,726 @@
+use crate::{
+    edit::EditLesson,
+    internal_error,
+    lesson::{
+        Lesson, LessonReadOptions, LessonStatus, LessonStatusRange, SortOrder, TranslateLesson,
+    },
+    lineage::SplitLesson,
+    log_error, message_text,
+    subscription::lesson_approved,
+    Error, Lang, Replier, ReplyResult, CONF, TEXT,
+};
+use sqlx::{query, PgPool};
+use std::{fmt::Write, str::FromStr};
+use teloxide::{
+    adaptors::AutoSend,
+    payloads::{EditMessageReplyMarkupSetters, EditMessageTextSetters, SendMessageSetters},
+    requests::{Requester, ResponseResult},
+    types::{
+        ChatId, InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, Message,
+    },
+    Bot,
+};
+
+const SET_STATUS_CMD: &str = "/set-status";
+/// Buttons under older messages still use it
+const OLD_SET_STATUS_CMD: &str = "/set-lesson-status";
+const REASONS_CMD: &str = "/reasons";
+/// Replied to a lesson message these change its status
+const APPROVE_CMD: &str = "/approve";
+const REJECT_CMD: &str = "/reject";
+const BEST_CMD: &str = "/best";
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SetLessonStatus {
+    /// Kind of lessons we're moderating
+    view: LessonReadOptions,
+    lesson_id: i32,
+    status: LessonStatus,
+    /// Why the lesson is rejected, one of `CONF.rejection_reasons`
+    reason: Option<String>,
+    /// The status the moderator saw, the change fails if someone has changed it since
+    prev_status: Option<LessonStatus>,
+}
+
+/// Shows the rejection reasons in place of the moderator buttons, or hides them back
+#[derive(Debug, PartialEq, Eq)]
+pub struct RejectionReasons {
+    pub(crate) view: LessonReadOptions,
+    pub(crate) lesson_id: i32,
+    pub(crate) prev_status: LessonStatus,
+    pub(crate) show: bool,
+}
+
+impl SetLessonStatus {
+    pub(crate) fn new(view: LessonReadOptions, lesson_id: i32, status: LessonStatus) -> Self {
+        Self {
+            view,
+            lesson_id,
+            status,
+            reason: None,
+            prev_status: None,
+        }
+    }
+
+    pub(crate) fn prev_status(mut self, prev_status: LessonStatus) -> Self {
+        self.prev_status = Some(prev_status);
+        self
+    }
+
+    pub(crate) fn reason(mut self, reason: &str) -> Self {
+        self.reason = Some(reason.to_owned());
+        self
+    }
+
+    /// Fails if the status the moderator saw can't move to the new one
+    fn check_transition(&self) -> Result<(), Error> {
+        match self.prev_status {
+            Some(prev) if !prev.can_become(self.status) => {
+                Err(Error::IllegalStatusChange(prev, self.status))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// `/approve`, `/reject [reason]` or `/best` in reply to a lesson message, the view
+    /// and the status the moderator saw come from its buttons
+    pub fn from_reply(message: &Message) -> Option<Self> {
+        let mut parts = message_text(message)?.split_whitespace();
+        // Groups may address the bot as `/approve@bot_username`
+        let name = parts.next()?.split('@').next()?;
+        let status = match name {
+            APPROVE_CMD => LessonStatus::Approved,
+            REJECT_CMD => LessonStatus::Rejected,
+            BEST_CMD => LessonStatus::Best,
+            _ => return None,
+        };
+        let reason = match (status, parts.next(), parts.next()) {
+            (_, None, None) => None,
+            (LessonStatus::Rejected, Some(reason), None) if reason.parse::<i32>().is_err() => {
+                Some(reason.to_owned())
+            }
+            _ => return None,
+        };
+        let buttons = message.reply_to_message()?.reply_markup()?;
+        let (view, lesson_id, prev_status) = keyboard_moderation(buttons)?;
+        Some(Self {
+            view,
+            lesson_id,
+            status,
+            reason,
+            prev_status,
+        })
+    }
+
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        if matches!(
+            cmd.split_whitespace().next(),
+            Some(SET_STATUS_CMD | OLD_SET_STATUS_CMD)
+        ) {
+            let mut parts = cmd.split_whitespace();
+            parts.next();
+            let view = parts.next().and_then(LessonReadOptions::from_view)?;
+            let lesson_id = parts.next().and_then(|s| s.parse().ok())?;
+            // `rejected:spam` carries the reason
+            let status = parts.next()?;
+            let (status, reason) = match status.split_once(':') {
+                Some((status, reason)) => (status, Some(reason.to_owned())),
+                None => (status, None),
+            };
+            let status = LessonStatus::from_str(status).ok()?;
+            if reason
+                .as_ref()
+                .is_some_and(|reason| reason.is_empty() || status != LessonStatus::Rejected)
+            {
+                return None;
+            }
+            let prev_status = match parts.next() {
+                Some(s) => Some(LessonStatus::from_str(s).ok()?),
+                None => None,
+            };
+            Some(Self {
+                view,
+                lesson_id,
+                status,
+                reason,
+                prev_status,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn to_command(&self) -> String {
+        let mut cmd = format!(
+            "{} {} {} {}",
+            SET_STATUS_CMD,
+            self.view.view(),
+            self.lesson_id,
+            self.status.as_ref()
+        );
+        if let Some(reason) = &self.reason {
+            write!(cmd, ":{reason}").ok();
+        }
+        if let Some(prev_status) = self.prev_status {
+            write!(cmd, " {}", prev_status.as_ref()).ok();
+        }
+        cmd
+    }
+
+    /// Returns a notice for the moderator. Best needs `CONF.best_quorum` votes unless an
+    /// admin decides.
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ResponseResult<String> {
+        let reader = repl.reader_key();
+        if let Err(e) = self.check_transition() {
+            return Ok(e.to_string());
+        }
+        let quorum = CONF.best_quorum as i64;
+        let mut changeable = true;
+        if self.status == LessonStatus::Best && quorum > 1 && !repl.is_admin() {
+            let moderator = match repl.sender_id() {
+                Some(moderator) => moderator,
+                None => return Ok(String::new()),
+            };
+            match Lesson::vote_best(pool, self.lesson_id, self.prev_status, moderator).await {
+                // Shown below like a status change that didn't happen
+                Ok(None) => changeable = false,
+                Ok(Some((false, votes))) if votes < quorum => {
+                    return Ok(format!("You have already voted {votes}/{quorum}"));
+                }
+                Ok(Some((true, votes))) if votes < quorum => {
+                    match Lesson::find(pool, self.lesson_id, repl.lang, reader.as_deref()).await {
+                        Ok(Some(lesson)) => {
+                            repl.edit_text(lesson.message(true, false))
+                                .reply_markup(lesson.moderator_keyboard(&self.view, repl))
+                                .await?;
+                        }
+                        Ok(None) => {
+                            repl.send_text(&TEXT.lesson_not_found).await?;
+                        }
+                        Err(e) => {
+                            repl.send_text(internal_error(&e)).await?;
+                        }
+                    }
+                    return Ok(format!("Vote for best recorded {votes}/{quorum}"));
+                }
+                Ok(Some(_)) => {}
+                Err(e) => {
+                    repl.send_text(internal_error(&e)).await?;
+                    return Ok(String::new());
+                }
+            }
+        }
+        let res = if changeable {
+            Lesson::set_status(
+                pool,
+                &[self.lesson_id],
+                self.status,
+                self.reason.as_deref(),
+                self.prev_status,
+                repl.sender_id(),
+            )
+            .await
+        } else {
+            Ok(vec![])
+        };
+        let updated = match res {
+            Ok(changed) => !changed.is_empty(),
+            Err(e @ (Error::IllegalStatusChange(..) | Error::UnknownRejectionReason(..))) => {
+                return Ok(e.to_string())
+            }
+            Err(e) => {
+                repl.send_text(internal_error(&e)).await?;
+                return Ok(String::new());
+            }
+        };
+        let lesson = match Lesson::find(pool, self.lesson_id, repl.lang, reader.as_deref()).await {
+            Ok(Some(lesson)) => lesson,
+            Ok(None) => {
+                repl.send_text(&TEXT.lesson_not_found).await?;
+                return Ok(String::new());
+            }
+            Err(e) => {
+                repl.send_text(internal_error(&e)).await?;
+                return Ok(String::new());
+            }
+        };
+        let mut text = lesson.message(true, false);
+        if updated && repl.in_moderators_chat() {
+            // The moderators chat shows who made the decision
+            let who = repl
+                .from
+                .as_ref()
+                .map(|u| u.mention().unwrap_or_else(|| u.full_name()))
+                .unwrap_or_default();
+            write!(text, "\n\n{}", self.status.label()).ok();
+            if let Some(reason) = &self.reason {
+                write!(text, ": {reason}").ok();
+            }
+            write!(text, " by {who}").ok();
+        }
+        let keyboard = lesson.moderator_keyboard(&self.view, repl);
+        let notice = if updated {
+            "Lesson status updated".to_owned()
+        } else if !lesson.status.can_become(self.status) {
+            let e = Error::IllegalStatusChange(lesson.status, self.status);
+            write!(text, "\n\n⚠️ {e}").ok();
+            e.to_string()
+        } else {
+            text.push_str("\n\n⚠️ Already moderated or claimed by someone else");
+            "Already moderated by someone else".to_owned()
+        };
+        repl.edit_text(text).reply_markup(keyboard).await?;
+        Ok(notice)
+    }
+}
+
+impl RejectionReasons {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let mut parts = cmd.split_whitespace();
+        if parts.next()? != REASONS_CMD {
+            return None;
+        }
+        let view = parts.next().and_then(LessonReadOptions::from_view)?;
+        let lesson_id = parts.next()?.parse().ok()?;
+        let prev_status = LessonStatus::from_str(parts.next()?).ok()?;
+        let show = match parts.next()? {
+            "show" => true,
+            "hide" => false,
+            _ => return None,
+        };
+        parts.next().is_none().then_some(Self {
+            view,
+            lesson_id,
+            prev_status,
+            show,
+        })
+    }
+
+    pub(crate) fn to_command(&self) -> String {
+        format!(
+            "{} {} {} {} {}",
+            REASONS_CMD,
+            self.view.view(),
+            self.lesson_id,
+            self.prev_status.as_ref(),
+            if self.show { "show" } else { "hide" }
+        )
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let keyboard = if self.show {
+            let mut lines = CONF
+                .rejection_reasons
+                .chunks(3)
+                .map(|reasons| {
+                    reasons
+                        .iter()
+                        .map(|reason| {
+                            InlineKeyboardButton::callback(
+                                reason,
+                                SetLessonStatus::new(
+                                    self.view,
+                                    self.lesson_id,
+                                    LessonStatus::Rejected,
+                                )
+                                .reason(reason)
+                                .prev_status(self.prev_status)
+                                .to_command(),
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            lines.push(vec![InlineKeyboardButton::callback(
+                "↩️ Back",
+                Self {
+                    show: false,
+                    ..*self
+                }
+                .to_command(),
+            )]);
+            InlineKeyboardMarkup::new(lines)
+        } else {
+            let reader = repl.reader_key();
+            match Lesson::find(pool, self.lesson_id, repl.lang, reader.as_deref()).await {
+                Ok(Some(lesson)) => lesson.moderator_keyboard(&self.view, repl),
+                Ok(None) => {
+                    repl.send_text(&TEXT.lesson_not_found).await?;
+                    return Ok(());
+                }
+                Err(e) => {
+                    repl.send_text(internal_error(&e)).await?;
+                    return Ok(());
+                }
+            }
+        };
+        repl.bot
+            .edit_message_reply_markup(repl.chat_id, repl.message_id)
+            .reply_markup(keyboard)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Finds the lesson a bot message is about by its moderator buttons
+pub(crate) fn keyboard_lesson_id(markup: &InlineKeyboardMarkup) -> Option<i32> {
+    markup
+        .inline_keyboard
+        .iter()
+        .flatten()
+        .find_map(|button| match &button.kind {
+            InlineKeyboardButtonKind::CallbackData(data) => SetLessonStatus::from_command(data)
+                .map(|cmd| cmd.lesson_id)
+                .or_else(|| TranslateLesson::from_command(data).map(|cmd| cmd.lesson_id)),
+            _ => None,
+        })
+}
+
+/// The view, lesson and its status from the moderator buttons of a lesson message
+fn keyboard_moderation(
+    markup: &InlineKeyboardMarkup,
+) -> Option<(LessonReadOptions, i32, Option<LessonStatus>)> {
+    markup
+        .inline_keyboard
+        .iter()
+        .flatten()
+        .find_map(|button| match &button.kind {
+            InlineKeyboardButtonKind::CallbackData(data) => SetLessonStatus::from_command(data)
+                .map(|cmd| (cmd.view, cmd.lesson_id, cmd.prev_status))
+                .or_else(|| {
+                    RejectionReasons::from_command(data)
+                        .map(|cmd| (cmd.view, cmd.lesson_id, Some(cmd.prev_status)))
+                }),
+            _ => None,
+        })
+}
+
+/// Posts a new lesson to the moderators chat, if there is one
+pub async fn post_to_moderators(pool: &PgPool, bot: &AutoSend<Bot>, lesson_id: i32) {
+    let chat_id = match CONF.moderators_chat {
+        Some(chat_id) => ChatId(chat_id),
+        None => return,
+    };
+    let lesson = match Lesson::find(pool, lesson_id, Lang::default(), None).await {
+        Ok(Some(lesson)) => lesson,
+        Ok(None) => return,
+        Err(e) => return log_error(&e),
+    };
+    let view = LessonReadOptions::new(LessonStatusRange::New, None).sort(SortOrder::Oldest);
+    let res = bot
+        .send_message(chat_id, lesson.message(true, false))
+        .disable_web_page_preview(true)
+        .reply_markup(lesson.moderation_keyboard(&view))
+        .await;
+    if let Err(e) = res {
+        log_error(&e);
+    }
+}
+
+impl Lesson {
+    /// Changes the status of the lessons unless they aren't in `prev_status` anymore, can't
+    /// move to the new status or another moderator has claimed them. Returns the changed
+    /// lessons. The `reason` must be one of `CONF.rejection_reasons`.
+    pub(crate) async fn set_status(
+        pool: &PgPool,
+        lesson_ids: &[i32],
+        status: LessonStatus,
+        reason: Option<&str>,
+        prev_status: Option<LessonStatus>,
+        moderator: Option<i64>,
+    ) -> Result<Vec<i32>, Error> {
+        if let Some(prev) = prev_status.filter(|prev| !prev.can_become(status)) {
+            return Err(Error::IllegalStatusChange(prev, status));
+        }
+        // The rejection report groups by the reasons
+        if let Some(reason) = reason.filter(|r| !CONF.rejection_reasons.iter().any(|c| c == r)) {
+            return Err(Error::UnknownRejectionReason(
+                reason.to_owned(),
+                CONF.rejection_reasons.join(", "),
+            ));
+        }
+        let changed = query!(
+            r#"
+            WITH l AS (
+                UPDATE lesson
+                SET status = $2, reason = $3, claimed_by = NULL, claimed_at = NULL
+                WHERE id = ANY($1)
+                  AND deleted_at IS NULL
+                  AND status = ANY($7)
+                  AND ($4::lesson_status IS NULL OR status = $4)
+                  AND (claimed_by IS NULL
+                    OR claimed_by = $5
+                    OR claimed_at < now() - $6::float8 * interval '1 second')
+                RETURNING id, status, reason
+            ), change AS (
+                INSERT INTO lesson_status_change (lesson_id, status, reason, moderator)
+                SELECT id, status, reason, $5 FROM l
+            ), votes AS (
+                DELETE FROM best_vote WHERE lesson_id IN (SELECT id FROM l)
+            )
+            SELECT id FROM l ORDER BY id
+            "#,
+            lesson_ids,
+            status as LessonStatus,
+            reason,
+            prev_status as Option<LessonStatus>,
+            moderator,
+            CONF.claim_timeout.as_secs_f64(),
+            LessonStatus::sources(status) as Vec<LessonStatus>,
+        )
+        .map(|r| r.id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| Error::SetLessonStatus(e, lesson_ids.to_vec(), status))?;
+        if !changed.is_empty() && status.is_public() {
+            lesson_approved();
+        }
+        Ok(changed)
+    }
+
+    /// Records a moderator's vote to make the lesson best, returns whether it's a new vote
+    /// and the number of votes. Returns `None` without voting if the lesson isn't in
+    /// `prev_status` anymore, can't become best or another moderator has claimed it.
+    /// Votes on a lesson take turns, so the one reaching the quorum counts all the others.
+    async fn vote_best(
+        pool: &PgPool,
+        lesson_id: i32,
+        prev_status: Option<LessonStatus>,
+        moderator: i64,
+    ) -> Result<Option<(bool, i64)>, Error> {
+        let res = async {
+            let mut tx = pool.begin().await?;
+            // A statement doesn't see what's committed while it waits for a lock, so the
+            // lesson is locked before the vote is counted
+            query!("SELECT id FROM lesson WHERE id = $1 FOR UPDATE", lesson_id)
+                .fetch_optional(&mut tx)
+                .await?;
+            let votes = query!(
+                r#"
+                WITH l AS (
+                    SELECT id FROM lesson
+                    WHERE id = $1
+                      AND deleted_at IS NULL
+                      AND status = ANY($3)
+                      AND ($4::lesson_status IS NULL OR status = $4)
+                      AND (claimed_by IS NULL
+                        OR claimed_by = $2
+                        OR claimed_at < now() - $5::float8 * interval '1 second')
+                ), vote AS (
+                    INSERT INTO best_vote (lesson_id, moderator) SELECT id, $2 FROM l
+                    ON CONFLICT DO NOTHING
+                    RETURNING moderator
+                )
+                SELECT
+                    EXISTS (SELECT 1 FROM l) as "votable!",
+                    EXISTS (SELECT 1 FROM vote) as "voted!",
+                    (SELECT count(*) FROM best_vote WHERE lesson_id = $1)
+                        + (SELECT count(*) FROM vote) as "votes!"
+                "#,
+                lesson_id,
+                moderator,
+                LessonStatus::sources(LessonStatus::Best) as Vec<LessonStatus>,
+                prev_status as Option<LessonStatus>,
+                CONF.claim_timeout.as_secs_f64(),
+            )
+            .map(|r| r.votable.then_some((r.voted, r.votes)))
+            .fetch_one(&mut tx)
+            .await?;
+            tx.commit().await?;
+            Ok(votes)
+        };
+        res.await.map_err(|e| Error::VoteBest(e, lesson_id))
+    }
+
+    /// Moderator buttons only, for the moderators chat
+    fn moderation_keyboard(&self, view: &LessonReadOptions) -> InlineKeyboardMarkup {
+        InlineKeyboardMarkup::new(self.moderator_buttons(view))
+    }
+
+    /// The keyboard a moderator sees after acting on the lesson message
+    fn moderator_keyboard(&self, view: &LessonReadOptions, repl: &Replier) -> InlineKeyboardMarkup {
+        if repl.in_moderators_chat() {
+            self.moderation_keyboard(view)
+        } else {
+            self.keyboard(view, repl.lang, true, false, None)
+        }
+    }
+
+    /// Buttons for the statuses the lesson may move to, then the tools
+    pub(crate) fn moderator_buttons(
+        &self,
+        view: &LessonReadOptions,
+    ) -> Vec<Vec<InlineKeyboardButton>> {
+        let mut line = vec![];
+        if self.status.transitions().is_empty() {
+            return vec![];
+        }
+        for (status, label) in [
+            (LessonStatus::Approved, "👍 Approve"),
+            (LessonStatus::Rejected, "👎 Reject"),
+            (LessonStatus::Best, "🏆 Mark best"),
+            (LessonStatus::NeedsEdit, "🛠 Needs edit"),
+            (LessonStatus::Duplicate, "♊ Duplicate"),
+            (LessonStatus::Archived, "📦 Archive"),
+            (LessonStatus::Withdrawn, "🚫 Withdrawn"),
+        ] {
+            if !self.status.can_become(status) {
+                continue;
+            }
+            let cmd = if status == LessonStatus::Rejected && !CONF.rejection_reasons.is_empty() {
+                RejectionReasons {
+                    view: *view,
+                    lesson_id: self.id,
+                    prev_status: self.status,
+                    show: true,
+                }
+                .to_command()
+            } else {
+                SetLessonStatus::new(*view, self.id, status)
+                    .prev_status(self.status)
+                    .to_command()
+            };
+            let label = if status == LessonStatus::Best && CONF.best_quorum > 1 {
+                format!("🏆 {}/{}", self.best_votes, CONF.best_quorum)
+            } else {
+                label.to_owned()
+            };
+            line.push(InlineKeyboardButton::callback(label, cmd));
+        }
+        let mut lines = line.chunks(4).map(<[_]>::to_vec).collect::<Vec<_>>();
+        let mut line = vec![];
+        if self.status.can_become(LessonStatus::Superseded) {
+            line.push(InlineKeyboardButton::callback(
+                "✂️ Split",
+                SplitLesson {
+                    lesson_id: self.id,
+                    cuts: 0,
+                    apply: false,
+                }
+                .to_command(),
+            ));
+        }
+        line.push(InlineKeyboardButton::callback(
+            "🌐 Translate",
+            TranslateLesson {
+                lesson_id: self.id,
+                translation: None,
+            }
+            .to_command(),
+        ));
+        line.push(InlineKeyboardButton::callback(
+            "✏️ Edit",
+            EditLesson {
+                lesson_id: self.id,
+                text: None,
+            }
+            .to_command(),
+        ));
+        lines.push(line);
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_lesson_status_from_command() {
+        assert!(SetLessonStatus::from_command("/unknown").is_none());
+        assert!(SetLessonStatus::from_command("/set-lesson-status").is_none());
+        assert!(SetLessonStatus::from_command("/set-lesson-status foo bar").is_none());
+        assert!(SetLessonStatus::from_command("/set-lesson-status 1 bar").is_none());
+        assert!(SetLessonStatus::from_command("/set-lesson-status foo best").is_none());
+        assert_eq!(
+            SetLessonStatus::from_command("/set-lesson-status approved 1 best").unwrap(),
+            SetLessonStatus::new(
+                LessonReadOptions::new(LessonStatusRange::Approved, None),
+                1,
+                LessonStatus::Best
+            )
+        );
+        assert_eq!(
+            SetLessonStatus::from_command("/set-lesson-status new+own 1 approved").unwrap(),
+            SetLessonStatus::new(
+                LessonReadOptions::new(LessonStatusRange::New, None).own_lang(true),
+                1,
+                LessonStatus::Approved
+            )
+        );
+        assert!(SetLessonStatus::from_command("/set-lesson-status n 1 approved foo").is_none());
+        let cmd = SetLessonStatus::new(
+            LessonReadOptions::new(LessonStatusRange::New, None),
+            1,
+            LessonStatus::Approved,
+        )
+        .prev_status(LessonStatus::New);
+        assert_eq!(cmd.to_command(), "/set-status n 1 approved new");
+        assert_eq!(SetLessonStatus::from_command(&cmd.to_command()), Some(cmd));
+
+        let cmd = SetLessonStatus::new(
+            LessonReadOptions::new(LessonStatusRange::New, None),
+            1,
+            LessonStatus::Rejected,
+        )
+        .reason("spam")
+        .prev_status(LessonStatus::New);
+        assert_eq!(cmd.to_command(), "/set-status n 1 rejected:spam new");
+        assert_eq!(SetLessonStatus::from_command(&cmd.to_command()), Some(cmd));
+        assert!(SetLessonStatus::from_command("/set-lesson-status n 1 rejected:").is_none());
+        assert!(SetLessonStatus::from_command("/set-lesson-status n 1 best:spam").is_none());
+    }
+
+    #[test]
+    fn set_lesson_status_checks_transition() {
+        let view = LessonReadOptions::new(LessonStatusRange::All, None);
+        let change = |prev| SetLessonStatus::new(view, 1, LessonStatus::Rejected).prev_status(prev);
+        assert!(change(LessonStatus::New).check_transition().is_ok());
+        assert!(change(LessonStatus::Best).check_transition().is_err());
+    }
+
+    #[test]
+    fn rejection_reasons_command() {
+        let cmd = RejectionReasons {
+            view: LessonReadOptions::new(LessonStatusRange::New, None),
+            lesson_id: 5,
+            prev_status: LessonStatus::New,
+            show: true,
+        };
+        assert_eq!(cmd.to_command(), "/reasons n 5 new show");
+        assert_eq!(RejectionReasons::from_command(&cmd.to_command()), Some(cmd));
+        assert!(RejectionReasons::from_command("/reasons n 5 new").is_none());
+        assert!(RejectionReasons::from_command("/reasons n 5 new show x").is_none());
+        assert!(RejectionReasons::from_command("/reasons n 5 foo hide").is_none());
+    }
+
+    #[test]
+    fn keyboard_lesson_id_finds_moderator_buttons() {
+        let view = LessonReadOptions::new(LessonStatusRange::New, None);
+        let reader_only = InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+            "Next",
+            view.to_command(),
+        )]]);
+        assert_eq!(keyboard_lesson_id(&reader_only), None);
+        let moderator = reader_only.append_row([InlineKeyboardButton::callback(
+            "👍 Approve",
+            SetLessonStatus::new(view, 7, LessonStatus::Approved).to_command(),
+        )]);
+        assert_eq!(keyboard_lesson_id(&moderator), Some(7));
+        assert_eq!(keyboard_moderation(&moderator), Some((view, 7, None)));
+        let reasons = InlineKeyboardMarkup::new([[InlineKeyboardButton::callback(
+            "👎 Reject",
+            RejectionReasons {
+                view,
+                lesson_id: 8,
+                prev_status: LessonStatus::New,
+                show: true,
+            }
+            .to_command(),
+        )]]);
+        assert_eq!(
+            keyboard_moderation(&reasons),
+            Some((view, 8, Some(LessonStatus::New)))
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{
+    internal_error, lesson::timeago, message_text, moderation::keyboard_lesson_id, Error, Replier,
+    ReplyResult,
+};
+use sqlx::{query, PgPool};
+use std::fmt::Write;
+use teloxide::types::Message;
+
+const NOTES_CMD: &str = "/notes";
+
+/// Moderators' notes on lessons
+#[derive(Debug, PartialEq, Eq)]
+pub enum NoteCommand {
+    /// A reply to a lesson message adds a note to the lesson
+    Add { lesson_id: i32, text: String },
+    /// `/notes <lesson id>` lists the notes on the lesson
+    List(i32),
+}
+
+impl NoteCommand {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let mut parts = cmd.split_whitespace();
+        match (parts.next()?, parts.next(), parts.next()) {
+            (NOTES_CMD, Some(id), None) => id.parse().ok().map(Self::List),
+            _ => None,
+        }
+    }
+
+    /// Recognizes a reply to a bot message with a lesson and its moderator buttons
+    pub fn from_reply(message: &Message) -> Option<Self> {
+        let lesson = message.reply_to_message()?;
+        let lesson_id = keyboard_lesson_id(lesson.reply_markup()?)?;
+        let text = message_text(message)?.trim();
+        (!text.is_empty()).then(|| Self::Add {
+            lesson_id,
+            text: text.to_owned(),
+        })
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        match self {
+            Self::Add { lesson_id, text } => {
+                let (author, author_name) = match &repl.from {
+                    Some(u) => (u.id.0 as i64, u.full_name()),
+                    None => return Ok(()),
+                };
+                let res = query!(
+                    r#"
+                    INSERT INTO lesson_note (lesson_id, author, author_name, text)
+                    VALUES ($1, $2, $3, $4)
+                    "#,
+                    lesson_id,
+                    author,
+                    author_name,
+                    text,
+                )
+                .execute(pool)
+                .await
+                .map_err(|e| Error::AddNote(e, *lesson_id));
+                match res {
+                    Ok(_) => repl.send_text("📝 Note added").await?,
+                    Err(e) => repl.send_text(internal_error(&e)).await?,
+                };
+            }
+            Self::List(lesson_id) => {
+                let res = query!(
+                    r#"
+                    SELECT author_name, text, created_at
+                    FROM lesson_note
+                    WHERE lesson_id = $1
+                    ORDER BY id
+                    "#,
+                    lesson_id,
+                )
+                .fetch_all(pool)
+                .await
+                .map_err(|e| Error::ListNotes(e, *lesson_id));
+                match res {
+                    Ok(notes) if notes.is_empty() => {
+                        repl.send_text(format!("No notes on lesson {lesson_id}"))
+                            .await?
+                    }
+                    Ok(notes) => {
+                        let mut text = format!("Notes on lesson {lesson_id}:");
+                        for note in notes {
+                            write!(
+                                text,
+                                "\n\n{}, {} ago:\n{}",
+                                note.author_name,
+                                timeago(note.created_at),
+                                note.text
+                            )
+                            .ok();
+                        }
+                        repl.send_text(text).await?
+                    }
+                    Err(e) => repl.send_text(internal_error(&e)).await?,
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn note_command_from_command() {
+        assert_eq!(
+            NoteCommand::from_command("/notes 12"),
+            Some(NoteCommand::List(12))
+        );
+        assert!(NoteCommand::from_command("/notes").is_none());
+        assert!(NoteCommand::from_command("/notes x").is_none());
+        assert!(NoteCommand::from_command("/notes 1 2").is_none());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{internal_error, log_error, start_keyboard, Error, Replier, ReplyResult, CONF, TEXT};
+use sqlx::{query, PgPool};
+use std::time::Duration;
+use teloxide::{
+    payloads::{EditMessageReplyMarkupSetters, SendMessageSetters},
+    requests::Requester,
+};
+
+const UNREAD_CMD: &str = "/unread";
+const RESET_READ_CMD: &str = "/reset-read";
+/// How often to forget expired read lessons
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Commands to manage the lessons a reader has seen
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReadStateCommand {
+    /// `/unread [on|off]` toggles the "unread only" start keyboard
+    UnreadOnly(bool),
+    /// `/reset-read` forgets the read lessons
+    Reset,
+}
+
+impl ReadStateCommand {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let mut parts = cmd.split_whitespace();
+        match (parts.next()?, parts.next(), parts.next()) {
+            (UNREAD_CMD, None | Some("on"), None) => Some(Self::UnreadOnly(true)),
+            (UNREAD_CMD, Some("off"), None) => Some(Self::UnreadOnly(false)),
+            (RESET_READ_CMD, None, None) => Some(Self::Reset),
+            _ => None,
+        }
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        match self {
+            Self::UnreadOnly(unread) => {
+                let keyboard = start_keyboard(pool, repl.lang, repl.is_moderator(), *unread).await;
+                if repl.callback {
+                    repl.bot
+                        .edit_message_reply_markup(repl.chat_id, repl.message_id)
+                        .reply_markup(keyboard)
+                        .await?;
+                } else {
+                    repl.send_html(&TEXT.help_message)
+                        .reply_markup(keyboard)
+                        .await?;
+                }
+            }
+            Self::Reset => {
+                let reader = match repl.reader_key() {
+                    Some(reader) => reader,
+                    None => return Ok(()),
+                };
+                let res = query!("DELETE FROM lesson_read WHERE reader = $1", reader)
+                    .execute(pool)
+                    .await
+                    .map_err(Error::ResetReadState);
+                match res {
+                    Ok(_) => repl.send_text(&TEXT.read_reset).await?,
+                    Err(e) => repl.send_text(internal_error(&e)).await?,
+                };
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Remembers that the reader has seen the lesson, seeing it again postpones the expiry
+pub(crate) async fn mark_read(pool: &PgPool, reader: &str, lesson_id: i32) {
+    let res = query!(
+        r#"
+        INSERT INTO lesson_read (reader, lesson_id) VALUES ($1, $2)
+        ON CONFLICT (reader, lesson_id) DO UPDATE SET read_at = now()
+        "#,
+        reader,
+        lesson_id,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| Error::MarkLessonRead(e, lesson_id));
+    if let Err(e) = res {
+        log_error(&e);
+    }
+}
+
+/// Forgets what readers have read after `read_state_lifetime`, so the history of a reader
+/// can't be restored for longer than that
+pub async fn purge_read_state(pool: PgPool) {
+    loop {
+        let res = query!(
+            "DELETE FROM lesson_read WHERE read_at < now() - $1::float8 * interval '1 second'",
+            CONF.read_state_lifetime.as_secs_f64(),
+        )
+        .execute(&pool)
+        .await
+        .map_err(Error::PurgeReadState);
+        if let Err(e) = res {
+            log_error(&e);
+        }
+        tokio::time::sleep(PURGE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_state_command_from_command() {
+        assert!(ReadStateCommand::from_command("/unknown").is_none());
+        assert!(ReadStateCommand::from_command("/unread maybe").is_none());
+        assert!(ReadStateCommand::from_command("/reset-read all").is_none());
+        assert_eq!(
+            ReadStateCommand::from_command("/unread"),
+            Some(ReadStateCommand::UnreadOnly(true))
+        );
+        assert_eq!(
+            ReadStateCommand::from_command("/unread off"),
+            Some(ReadStateCommand::UnreadOnly(false))
+        );
+        assert_eq!(
+            ReadStateCommand::from_command("/reset-read"),
+            Some(ReadStateCommand::Reset)
+        );
+    }
+}
@@ -0,0 +1,28 @@
+use crate::CONF;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Returns a stable pseudonym of a reader to keep their bookmarks. Unlike spam tokens it never
+/// changes, but without the server secret it can't be linked to the telegram ID.
+pub fn reader_key(user_id: i64) -> String {
+    keyed_hash(CONF.reader_secret.as_bytes(), user_id)
+}
+
+fn keyed_hash(secret: &[u8], user_id: i64) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    mac.update(&user_id.to_be_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyed_per_user_and_secret() {
+        assert_eq!(keyed_hash(b"secret", 1), keyed_hash(b"secret", 1));
+        assert_ne!(keyed_hash(b"secret", 1), keyed_hash(b"secret", 2));
+        assert_ne!(keyed_hash(b"secret", 1), keyed_hash(b"other", 1));
+        assert_eq!(keyed_hash(b"secret", 1).len(), 64);
+    }
+}
@@ -1,10 +1,10 @@
-use crate::{Lang, Translate, CONF};
+use crate::{reader_key, Lang, Translate, CONF};
 use teloxide::{
     adaptors::AutoSend,
-    payloads::SendMessageSetters,
+    payloads::{EditMessageTextSetters, SendMessageSetters},
     requests::{Requester, ResponseResult},
-    types::{CallbackQuery, ChatId, Message, ParseMode},
-    Bot,
+    types::{CallbackQuery, ChatId, InlineKeyboardMarkup, Message, ParseMode, User},
+    ApiError, Bot, RequestError,
 };
 
 pub type Reply = <AutoSend<Bot> as Requester>::SendMessage;
@@ -16,6 +16,10 @@ pub struct Replier {
     pub message_id: i32,
     pub chat_id: ChatId,
     pub lang: Lang,
+    /// Replying to a button pressed under the `message_id` bot message
+    pub callback: bool,
+    /// Who sent the message or pressed the button, it differs from the chat in groups
+    pub from: Option<User>,
 }
 
 impl Replier {
@@ -25,13 +29,17 @@ impl Replier {
             message_id: message.id,
             chat_id: message.chat.id,
             lang: Lang::from(message),
+            callback: false,
+            from: message.from().cloned(),
         }
     }
 
     pub fn from_callback_query(bot: AutoSend<Bot>, q: &CallbackQuery) -> Option<Self> {
         if let Some(message) = &q.message {
-            let mut repl = Self::from_message(bot, &message);
+            let mut repl = Self::from_message(bot, message);
             repl.lang = Lang::from(&q.from);
+            repl.callback = true;
+            repl.from = Some(q.from.clone());
             Some(repl)
         } else {
             None
@@ -46,10 +54,41 @@ impl Replier {
         }
     }
 
+    /// A pseudonym of the user to keep their bookmarks, see `reader_key`
+    pub fn reader_key(&self) -> Option<String> {
+        self.user_id().map(reader_key)
+    }
+
+    /// Moderators act in private chats and in the moderators chat
     pub fn is_moderator(&self) -> bool {
-        self.user_id()
-            .map(|u| CONF.moderators.contains(&u))
-            .unwrap_or_default()
+        self.in_staff_chat()
+            && self
+                .sender_id()
+                .map(|u| CONF.moderators.contains(&u))
+                .unwrap_or_default()
+    }
+
+    /// The telegram ID of the user who sent the message or pressed the button
+    pub fn sender_id(&self) -> Option<i64> {
+        self.from.as_ref().map(|u| u.id.0 as i64)
+    }
+
+    /// Whether the bot is replying in the moderators chat
+    pub fn in_moderators_chat(&self) -> bool {
+        CONF.moderators_chat == Some(self.chat_id.0)
+    }
+
+    pub fn is_admin(&self) -> bool {
+        self.in_staff_chat()
+            && self
+                .sender_id()
+                .map(|u| CONF.admins.contains(&u))
+                .unwrap_or_default()
+    }
+
+    /// Moderators and admins act in private chats and the moderators chat
+    fn in_staff_chat(&self) -> bool {
+        self.user_id().is_some() || self.in_moderators_chat()
     }
 
     pub fn send_text(&self, text: impl Translate) -> Reply {
@@ -71,4 +110,19 @@ impl Replier {
         self.bot
             .edit_message_text(self.chat_id, self.message_id, text)
     }
+
+    /// Edits the message, or sends a new one if it's too old to edit
+    pub async fn edit_or_send(&self, text: String, markup: InlineKeyboardMarkup) -> ReplyResult {
+        match self
+            .edit_text(text.clone())
+            .reply_markup(markup.clone())
+            .await
+        {
+            Ok(_) | Err(RequestError::Api(ApiError::MessageNotModified)) => {}
+            Err(_) => {
+                self.send_text(text).reply_markup(markup).await?;
+            }
+        }
+        Ok(())
+    }
 }
@@ -0,0 +1,87 @@
+use crate::{internal_error, Error, Replier, ReplyResult};
+use sqlx::{query, PgPool};
+use std::fmt::Write;
+
+const REJECTIONS_CMD: &str = "/rejections";
+const DEFAULT_WEEKS: i32 = 4;
+const MAX_WEEKS: i32 = 52;
+
+/// `/rejections [weeks]` counts rejected lessons per reason and week
+#[derive(Debug, PartialEq, Eq)]
+pub struct RejectionReport {
+    weeks: i32,
+}
+
+impl RejectionReport {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let mut parts = cmd.split_whitespace();
+        let weeks = match (parts.next()?, parts.next(), parts.next()) {
+            (REJECTIONS_CMD, None, None) => DEFAULT_WEEKS,
+            (REJECTIONS_CMD, Some(weeks), None) => weeks.parse().ok()?,
+            _ => return None,
+        };
+        (1..=MAX_WEEKS).contains(&weeks).then_some(Self { weeks })
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let res = query!(
+            r#"
+            SELECT
+                date_trunc('week', changed_at) as "week!",
+                coalesce(reason, 'unspecified') as "reason!",
+                count(*) as "count!"
+            FROM lesson_status_change
+            WHERE status = 'rejected'
+              AND changed_at >= date_trunc('week', now()) - ($1 - 1) * interval '1 week'
+            GROUP BY 1, 2
+            ORDER BY 1 DESC, 3 DESC, 2
+            "#,
+            self.weeks as f64,
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(Error::RejectionReport);
+        let rows = match res {
+            Ok(rows) => rows,
+            Err(e) => {
+                repl.send_text(internal_error(&e)).await?;
+                return Ok(());
+            }
+        };
+        let mut text = format!("Rejections per reason, last {} week(s):", self.weeks);
+        if rows.is_empty() {
+            text.push_str("\nnone");
+        }
+        let mut week = None;
+        for row in rows {
+            if week != Some(row.week) {
+                week = Some(row.week);
+                write!(text, "\n\nweek of {}:", row.week.date()).ok();
+            }
+            write!(text, "\n{} {}", row.reason, row.count).ok();
+        }
+        repl.send_text(text).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejection_report_from_command() {
+        assert_eq!(
+            RejectionReport::from_command("/rejections"),
+            Some(RejectionReport { weeks: 4 })
+        );
+        assert_eq!(
+            RejectionReport::from_command("/rejections 12"),
+            Some(RejectionReport { weeks: 12 })
+        );
+        assert!(RejectionReport::from_command("/rejections 0").is_none());
+        assert!(RejectionReport::from_command("/rejections 53").is_none());
+        assert!(RejectionReport::from_command("/rejections x").is_none());
+        assert!(RejectionReport::from_command("/rejections 1 2").is_none());
+    }
+}
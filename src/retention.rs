@@ -0,0 +1,249 @@
+use crate::{internal_error, lesson::LessonStatus, log_error, Error, Replier, ReplyResult, CONF};
+use serde::Deserialize;
+use sqlx::{query, PgPool};
+use std::{fmt::Write, str::FromStr, time::Duration};
+
+const RETENTION_CMD: &str = "/retention";
+/// How often to trash the lessons and forget the spam tokens past their retention
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How long lessons keep a status before they go to the trash, `rejected:90days` in the config
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct RetentionPolicy {
+    pub status: LessonStatus,
+    pub age: Duration,
+}
+
+impl FromStr for RetentionPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (status, age) = s
+            .trim()
+            .split_once(':')
+            .ok_or_else(|| format!("`{s}` isn't like `rejected:90days`"))?;
+        let status = LessonStatus::from_str(status).map_err(|_| format!("No status `{status}`"))?;
+        if status.is_public() || matches!(status, LessonStatus::New | LessonStatus::NeedsEdit) {
+            return Err(format!(
+                "`{status:?}` lessons can't expire, they're read or moderated"
+            ));
+        }
+        let age = humantime::parse_duration(age).map_err(|e| format!("`{age}`: {e}"))?;
+        Ok(Self { status, age })
+    }
+}
+
+impl TryFrom<String> for RetentionPolicy {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// `/retention` shows what the retention job would trash now without trashing it
+#[derive(Debug, PartialEq, Eq)]
+pub struct RetentionReport;
+
+impl RetentionReport {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        (cmd.trim() == RETENTION_CMD).then_some(Self)
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        let (statuses, ages) = policies();
+        let res = async {
+            let lessons = query!(
+                r#"
+                SELECT p.status as "status!: LessonStatus", count(l.id) as "count!"
+                FROM unnest($1::lesson_status[], $2::float8[]) AS p (status, age)
+                LEFT JOIN lesson l ON l.status = p.status
+                  AND l.deleted_at IS NULL
+                  AND coalesce(
+                      greatest(
+                          (SELECT max(c.changed_at) FROM lesson_status_change c
+                           WHERE c.lesson_id = l.id),
+                          (SELECT max(d.done_at) FROM lesson_deletion d
+                           WHERE d.lesson_id = l.id AND d.action = 'restore')
+                      ),
+                      l.created_at
+                  ) < now() - p.age * interval '1 second'
+                GROUP BY p.status
+                "#,
+                &statuses as &[LessonStatus],
+                &ages,
+            )
+            .fetch_all(pool)
+            .await?;
+            let spam_tokens = query!(
+                r#"
+                SELECT count(*) as "count!"
+                FROM lesson
+                WHERE spam_token IS NOT NULL
+                  AND created_at < now() - $1::float8 * interval '1 second'
+                "#,
+                CONF.rate_limit_duration.as_secs_f64(),
+            )
+            .fetch_one(pool)
+            .await?
+            .count;
+            Ok((lessons, spam_tokens))
+        }
+        .await
+        .map_err(Error::RetentionReport);
+        let (lessons, spam_tokens) = match res {
+            Ok(counts) => counts,
+            Err(e) => {
+                repl.send_text(internal_error(&e)).await?;
+                return Ok(());
+            }
+        };
+        let mut text = "🧹 Dry run, the retention job would now trash:".to_owned();
+        for policy in &CONF.retention_policies {
+            let count = lessons
+                .iter()
+                .find(|l| l.status == policy.status)
+                .map_or(0, |l| l.count);
+            write!(
+                text,
+                "\n{} lessons {:?} for {}",
+                count,
+                policy.status,
+                humantime::format_duration(policy.age)
+            )
+            .ok();
+        }
+        write!(
+            text,
+            "\n{} spam tokens older than {}",
+            spam_tokens,
+            humantime::format_duration(CONF.rate_limit_duration)
+        )
+        .ok();
+        repl.send_text(text).await?;
+        Ok(())
+    }
+}
+
+fn policies() -> (Vec<LessonStatus>, Vec<f64>) {
+    CONF.retention_policies
+        .iter()
+        .map(|p| (p.status, p.age.as_secs_f64()))
+        .unzip()
+}
+
+/// Moves the lessons that have kept a status longer than its `retention_policies` age since
+/// the last status change or restore to the trash, `purge_trash` purges them after
+/// `trash_retention`.
+/// Forgets spam tokens once they can't limit the rate anymore.
+pub async fn apply_retention(pool: PgPool) {
+    let (statuses, ages) = policies();
+    loop {
+        let res = query!(
+            r#"
+            WITH trashed AS (
+                UPDATE lesson l
+                SET deleted_at = now(), claimed_by = NULL, claimed_at = NULL
+                FROM unnest($1::lesson_status[], $2::float8[]) AS p (status, age)
+                WHERE l.status = p.status
+                  AND l.deleted_at IS NULL
+                  AND coalesce(
+                      greatest(
+                          (SELECT max(c.changed_at) FROM lesson_status_change c
+                           WHERE c.lesson_id = l.id),
+                          (SELECT max(d.done_at) FROM lesson_deletion d
+                           WHERE d.lesson_id = l.id AND d.action = 'restore')
+                      ),
+                      l.created_at
+                  ) < now() - p.age * interval '1 second'
+                RETURNING l.id, l.status
+            )
+            INSERT INTO lesson_deletion (lesson_id, action, reason)
+            SELECT id, 'delete', 'retention of ' || status || ' lessons' FROM trashed
+            "#,
+            &statuses as &[LessonStatus],
+            &ages,
+        )
+        .execute(&pool)
+        .await
+        .map_err(Error::ApplyRetention);
+        match res {
+            Ok(done) if done.rows_affected() > 0 => {
+                log::info!("Trashed {} lessons past retention", done.rows_affected())
+            }
+            Ok(_) => {}
+            Err(e) => log_error(&e),
+        }
+        let res = query!(
+            r#"
+            UPDATE lesson SET spam_token = NULL
+            WHERE spam_token IS NOT NULL
+              AND created_at < now() - $1::float8 * interval '1 second'
+            "#,
+            CONF.rate_limit_duration.as_secs_f64(),
+        )
+        .execute(&pool)
+        .await
+        .map_err(Error::ApplyRetention);
+        if let Err(e) = res {
+            log_error(&e);
+        }
+        tokio::time::sleep(PURGE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retention_policy_from_str() {
+        assert_eq!(
+            "rejected:90days".parse(),
+            Ok(RetentionPolicy {
+                status: LessonStatus::Rejected,
+                age: Duration::from_secs(90 * 24 * 60 * 60),
+            })
+        );
+        assert!("rejected".parse::<RetentionPolicy>().is_err());
+        assert!("unknown:1day".parse::<RetentionPolicy>().is_err());
+        assert!("rejected:soon".parse::<RetentionPolicy>().is_err());
+        assert!("approved:1day".parse::<RetentionPolicy>().is_err());
+        assert!("new:1day".parse::<RetentionPolicy>().is_err());
+    }
+
+    #[test]
+    fn retention_policies_from_env() {
+        #[derive(Deserialize)]
+        struct Config {
+            retention_policies: Vec<RetentionPolicy>,
+        }
+        let conf: Config = envy::from_iter([(
+            "RETENTION_POLICIES".to_owned(),
+            "rejected:90days, withdrawn:1year".to_owned(),
+        )])
+        .unwrap();
+        assert_eq!(
+            conf.retention_policies
+                .iter()
+                .map(|p| p.status)
+                .collect::<Vec<_>>(),
+            [LessonStatus::Rejected, LessonStatus::Withdrawn]
+        );
+        assert!(envy::from_iter::<_, Config>([(
+            "RETENTION_POLICIES".to_owned(),
+            "best:1day".to_owned()
+        )])
+        .is_err());
+    }
+
+    #[test]
+    fn retention_report_from_command() {
+        assert_eq!(
+            RetentionReport::from_command("/retention"),
+            Some(RetentionReport)
+        );
+        assert!(RetentionReport::from_command("/retention now").is_none());
+    }
+}
@@ -0,0 +1,314 @@
+use crate::{internal_error, log_error, Error, Lang, Replier, ReplyResult, TEXT};
+use once_cell::sync::Lazy;
+use sqlx::{query, PgPool};
+use std::time::Duration;
+use teloxide::{
+    adaptors::AutoSend, payloads::SendMessageSetters, requests::Requester, types::ChatId, ApiError,
+    Bot, RequestError,
+};
+use tokio::sync::Notify;
+
+const SUBSCRIBE_CMD: &str = "/subscribe";
+const UNSUBSCRIBE_CMD: &str = "/unsubscribe";
+const SUBSCRIPTIONS_CMD: &str = "/subscriptions";
+const MAX_KEYWORDS: usize = 20;
+const MIN_KEYWORD_LEN: usize = 3;
+const MAX_KEYWORD_LEN: usize = 50;
+/// A pause between notifications to stay below telegram limits of ~30 messages per second
+const NOTIFY_DELAY: Duration = Duration::from_millis(100);
+/// How often to check for approved lessons if nobody wakes the notifier up
+const NOTIFY_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Wakes the notifier up when a lesson gets approved
+static APPROVED: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Opt-in notifications about new lessons containing keywords
+#[derive(Debug, PartialEq, Eq)]
+pub enum SubscriptionCommand {
+    /// `/subscribe <keywords>` adds keywords, without them explains what we store
+    Subscribe(Vec<String>),
+    /// `/unsubscribe [keywords]` removes the keywords or all subscriptions of the chat
+    Unsubscribe(Vec<String>),
+    /// `/subscriptions` lists the keywords
+    List,
+}
+
+impl SubscriptionCommand {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let (name, args) = cmd.split_once(char::is_whitespace).unwrap_or((cmd, ""));
+        match name {
+            SUBSCRIBE_CMD => Some(Self::Subscribe(keywords(args))),
+            UNSUBSCRIBE_CMD => Some(Self::Unsubscribe(keywords(args))),
+            SUBSCRIPTIONS_CMD if args.trim().is_empty() => Some(Self::List),
+            _ => None,
+        }
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        // Chat IDs are stored for private chats only, where the reader has opted in
+        let chat_id = match repl.user_id() {
+            Some(chat_id) => chat_id,
+            None => return Ok(()),
+        };
+        let res = match self {
+            Self::Subscribe(keywords) if keywords.is_empty() => {
+                repl.send_text(&TEXT.subscribe_help).await?;
+                return Ok(());
+            }
+            Self::Subscribe(keywords) => subscribe(pool, chat_id, keywords, repl.lang).await,
+            Self::Unsubscribe(keywords) => unsubscribe(pool, chat_id, keywords, repl.lang).await,
+            Self::List => list(pool, chat_id, repl.lang).await,
+        };
+        match res {
+            Ok(text) => repl.send_text(text).await?,
+            Err(e) => repl.send_text(internal_error(&e)).await?,
+        };
+        Ok(())
+    }
+}
+
+/// Keywords separated by commas, or by spaces if there are no commas
+fn keywords(args: &str) -> Vec<String> {
+    let mut keywords: Vec<String> = if args.contains(',') {
+        args.split(',').map(str::to_owned).collect()
+    } else {
+        args.split_whitespace().map(str::to_owned).collect()
+    };
+    keywords = keywords
+        .into_iter()
+        .map(|k| {
+            k.split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .to_lowercase()
+        })
+        .filter(|k| !k.is_empty())
+        .collect();
+    keywords.sort();
+    keywords.dedup();
+    keywords
+}
+
+async fn subscribe(
+    pool: &PgPool,
+    chat_id: i64,
+    keywords: &[String],
+    lang: Lang,
+) -> Result<String, Error> {
+    let bad_len = keywords.iter().any(|k| {
+        let len = k.chars().count();
+        !(MIN_KEYWORD_LEN..=MAX_KEYWORD_LEN).contains(&len)
+    });
+    let count = query!(
+        r#"
+        SELECT count(*) as "count!"
+        FROM subscription
+        WHERE chat_id = $1 AND keyword <> ALL($2)
+        "#,
+        chat_id,
+        keywords,
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(Error::Subscribe)?
+    .count;
+    if bad_len || count as usize + keywords.len() > MAX_KEYWORDS {
+        return Ok(TEXT.bad_keywords.to(lang));
+    }
+    query!(
+        r#"
+        INSERT INTO subscription (chat_id, keyword, lang)
+        SELECT $1, keyword, $3 FROM unnest($2::text[]) keyword
+        ON CONFLICT (chat_id, keyword) DO UPDATE SET lang = $3
+        "#,
+        chat_id,
+        keywords,
+        lang.code(),
+    )
+    .execute(pool)
+    .await
+    .map_err(Error::Subscribe)?;
+    Ok(TEXT.subscribed.to(lang).replace("{}", &keywords.join(", ")))
+}
+
+async fn unsubscribe(
+    pool: &PgPool,
+    chat_id: i64,
+    keywords: &[String],
+    lang: Lang,
+) -> Result<String, Error> {
+    query!(
+        r#"
+        DELETE FROM subscription
+        WHERE chat_id = $1 AND (cardinality($2::text[]) = 0 OR keyword = ANY($2))
+        "#,
+        chat_id,
+        keywords,
+    )
+    .execute(pool)
+    .await
+    .map_err(Error::Unsubscribe)?;
+    Ok(TEXT.unsubscribed.to(lang))
+}
+
+async fn list(pool: &PgPool, chat_id: i64, lang: Lang) -> Result<String, Error> {
+    let keywords: Vec<String> = query!(
+        "SELECT keyword FROM subscription WHERE chat_id = $1 ORDER BY keyword",
+        chat_id,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(Error::ListSubscriptions)?
+    .into_iter()
+    .map(|r| r.keyword)
+    .collect();
+    if keywords.is_empty() {
+        Ok(TEXT.no_subscriptions.to(lang))
+    } else {
+        Ok(TEXT
+            .subscriptions
+            .to(lang)
+            .replace("{}", &keywords.join(", ")))
+    }
+}
+
+/// Tells the notifier to look for approved lessons
+pub(crate) fn lesson_approved() {
+    APPROVED.notify_one();
+}
+
+/// Sends approved lessons to subscribers, each lesson is sent once
+pub async fn notify_subscribers(bot: AutoSend<Bot>, pool: PgPool) {
+    loop {
+        if let Err(e) = notify_approved(&bot, &pool).await {
+            log_error(&e);
+        }
+        tokio::select! {
+            _ = APPROVED.notified() => {}
+            _ = tokio::time::sleep(NOTIFY_POLL_INTERVAL) => {}
+        }
+    }
+}
+
+async fn notify_approved(bot: &AutoSend<Bot>, pool: &PgPool) -> Result<(), Error> {
+    loop {
+        // Marking the lesson first, a crash may lose notifications but never repeats them
+        let lesson = query!(
+            r#"
+            UPDATE lesson SET notified_at = now()
+            WHERE id = (
+                SELECT id FROM lesson
+                WHERE status IN ('approved', 'best') AND notified_at IS NULL AND deleted_at IS NULL
+                ORDER BY id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, text
+            "#
+        )
+        .fetch_optional(pool)
+        .await
+        .map_err(Error::NotifySubscribers)?;
+        let lesson = match lesson {
+            Some(lesson) => lesson,
+            None => return Ok(()),
+        };
+        let subscribers = query!(
+            r#"
+            SELECT DISTINCT ON (chat_id) chat_id, keyword, lang
+            FROM subscription
+            WHERE strpos(lower($1), keyword) > 0
+            ORDER BY chat_id, keyword
+            "#,
+            lesson.text,
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(Error::NotifySubscribers)?;
+        for s in subscribers {
+            let lang = Lang::from(s.lang);
+            let text = format!(
+                "{}\n\n{}",
+                TEXT.new_lesson_notification
+                    .to(lang)
+                    .replace("{}", &s.keyword),
+                lesson.text
+            );
+            if let Err(e) = notify(bot, ChatId(s.chat_id), &text).await {
+                if is_unreachable(&e) {
+                    query!("DELETE FROM subscription WHERE chat_id = $1", s.chat_id)
+                        .execute(pool)
+                        .await
+                        .map_err(Error::Unsubscribe)?;
+                } else {
+                    log_error(&e);
+                }
+            }
+            tokio::time::sleep(NOTIFY_DELAY).await;
+        }
+    }
+}
+
+/// Sends a message retrying once after a flood wait
+async fn notify(bot: &AutoSend<Bot>, chat_id: ChatId, text: &str) -> Result<(), RequestError> {
+    let send = || {
+        bot.send_message(chat_id, text)
+            .disable_web_page_preview(true)
+    };
+    match send().await {
+        Err(RequestError::RetryAfter(wait)) => {
+            tokio::time::sleep(wait).await;
+            send().await.map(|_| ())
+        }
+        res => res.map(|_| ()),
+    }
+}
+
+/// The user blocked the bot or deleted the account, there is no one to notify
+fn is_unreachable(e: &RequestError) -> bool {
+    matches!(
+        e,
+        RequestError::Api(
+            ApiError::BotBlocked
+                | ApiError::UserDeactivated
+                | ApiError::ChatNotFound
+                | ApiError::BotKicked
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subscription_command_from_command() {
+        assert!(SubscriptionCommand::from_command("/subscriptions all").is_none());
+        assert_eq!(
+            SubscriptionCommand::from_command("/subscribe"),
+            Some(SubscriptionCommand::Subscribe(vec![]))
+        );
+        assert_eq!(
+            SubscriptionCommand::from_command("/subscribe Donations  protest donations"),
+            Some(SubscriptionCommand::Subscribe(vec![
+                "donations".into(),
+                "protest".into()
+            ]))
+        );
+        assert_eq!(
+            SubscriptionCommand::from_command("/subscribe  Mobilization,  write  letters ,"),
+            Some(SubscriptionCommand::Subscribe(vec![
+                "mobilization".into(),
+                "write letters".into()
+            ]))
+        );
+        assert_eq!(
+            SubscriptionCommand::from_command("/unsubscribe"),
+            Some(SubscriptionCommand::Unsubscribe(vec![]))
+        );
+        assert_eq!(
+            SubscriptionCommand::from_command("/subscriptions"),
+            Some(SubscriptionCommand::List)
+        );
+    }
+}
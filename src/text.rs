@@ -1,17 +1,32 @@
+use crate::{
+    html::{self, HtmlError},
+    text_override, Error,
+};
 use once_cell::sync::Lazy;
 use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
 use teloxide::types::{Message, MessageKind, User};
 
-pub static TEXT: Lazy<Text> =
-    Lazy::new(|| toml::from_str(include_str!("./text.toml")).expect("`TEXT` from yaml"));
+const TEXT_TOML: &str = include_str!("./text.toml");
 
-#[derive(Clone, Copy, Debug, Default)]
-pub enum Lang {
-    #[default]
-    En,
-    Ru,
-    Ua,
-}
+/// Every text must be translated to this language, it ends all fallback chains
+pub const DEFAULT_LANG: &str = "en";
+
+/// Texts sent with `Replier::send_html`
+pub const HTML_TEXTS: &[&str] = &["help_message"];
+
+/// A field we inject into every text table to let `Translations` know its key
+const KEY_FIELD: &str = "_key";
+
+pub static TEXT: Lazy<Text> = Lazy::new(|| Text::from_toml(TEXT_TOML).expect("`TEXT` from toml"));
+
+/// Languages discovered from the translations in `text.toml`
+pub static LANGS: Lazy<Languages> =
+    Lazy::new(|| Languages::from_toml(TEXT_TOML).expect("`LANGS` from toml"));
+
+/// A supported language, identified by its key in `text.toml`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Lang(&'static str);
 
 #[derive(Deserialize)]
 pub struct Text {
@@ -28,50 +43,292 @@ pub struct Text {
     pub read_approved: Translations,
     pub read_best: Translations,
     pub next_lesson: Translations,
+    pub share: Translations,
+    pub more_lessons: Translations,
+    pub browse_lessons: Translations,
+    pub sort_newest: Translations,
+    pub sort_oldest: Translations,
+    pub sort_score: Translations,
+    pub save_lesson: Translations,
+    pub unsave_lesson: Translations,
+    pub saved_lessons: Translations,
+    pub unread_only: Translations,
+    pub include_read: Translations,
+    pub read_reset: Translations,
+    pub subscribe_help: Translations,
+    pub subscribed: Translations,
+    pub unsubscribed: Translations,
+    pub subscriptions: Translations,
+    pub no_subscriptions: Translations,
+    pub bad_keywords: Translations,
+    pub new_lesson_notification: Translations,
+    pub random_lesson: Translations,
+    pub another_random: Translations,
+    pub lesson_of_the_day: Translations,
+    pub own_language: Translations,
+    pub all_languages: Translations,
+    pub show_original: Translations,
+    pub show_translation: Translations,
     pub text_only: Translations,
     pub unknown_command: Translations,
 }
 
+/// Translations of a text keyed by the language
 #[derive(Deserialize)]
 pub struct Translations {
-    en: String,
-    ru: String,
-    ua: String,
+    #[serde(rename = "_key")]
+    key: String,
+    #[serde(flatten)]
+    texts: HashMap<String, String>,
 }
 
-impl Translations {
-    pub fn to(&self, lang: Lang) -> &str {
-        match lang {
-            Lang::En => &self.en,
-            Lang::Ru => &self.ru,
-            Lang::Ua => &self.ua,
+/// Why a text can't replace the built-in one
+#[derive(Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
+pub enum TextError {
+    /// Unknown text {0}
+    Unknown(String),
+    /// Invalid HTML: {0}
+    Html(#[from] HtmlError),
+    /// The text should contain {0} `{{}}` placeholder(s)
+    Placeholders(usize),
+    /// HTML tags should be the same as in the source: {0}
+    Tags(String),
+}
+
+#[derive(Debug)]
+pub struct Languages {
+    /// Language key → settings
+    langs: BTreeMap<String, Language>,
+    /// Built-in texts: text key → language → text
+    texts: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Default)]
+struct Language {
+    /// Telegram `language_code`s served by the language
+    codes: Vec<String>,
+    /// The language itself followed by its fallbacks
+    chain: Vec<String>,
+}
+
+/// Optional language settings from the `[languages]` table of `text.toml`
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LanguageConf {
+    /// Telegram `language_code`s to map to the language besides its key
+    #[serde(default)]
+    codes: Vec<String>,
+    /// A language to take missing texts from
+    fallback: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TextFile {
+    #[serde(default)]
+    languages: BTreeMap<String, LanguageConf>,
+    #[serde(flatten)]
+    texts: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Lang {
+    /// The language key in `text.toml`
+    pub fn code(self) -> &'static str {
+        self.0
+    }
+
+    /// The language key followed by the telegram `language_code`s it serves
+    pub fn codes(self) -> Vec<&'static str> {
+        let mut codes = vec![self.0];
+        if let Some(lang) = LANGS.langs.get(self.0) {
+            codes.extend(lang.codes.iter().map(String::as_str));
+        }
+        codes
+    }
+
+    /// The language followed by its fallbacks
+    pub fn chain(self) -> impl Iterator<Item = &'static str> {
+        LANGS.chain(self.0).iter().map(String::as_str)
+    }
+}
+
+impl Default for Lang {
+    fn default() -> Self {
+        Self(DEFAULT_LANG)
+    }
+}
+
+impl Text {
+    fn from_toml(src: &str) -> Result<Self, Error> {
+        let mut table: toml::value::Table = toml::from_str(src).map_err(Error::ParseText)?;
+        for (key, value) in table.iter_mut() {
+            if let toml::Value::Table(translations) = value {
+                translations.insert(KEY_FIELD.into(), key.clone().into());
+            }
         }
+        Self::deserialize(toml::Value::Table(table)).map_err(Error::ParseText)
+    }
+}
+
+impl Translations {
+    /// Returns the text in the language or its fallbacks, overrides take precedence
+    pub fn to(&self, lang: Lang) -> String {
+        lang.chain()
+            .find_map(|l| text_override::get(&self.key, l).or_else(|| self.texts.get(l).cloned()))
+            .unwrap_or_default()
     }
 
-    pub fn to_default(&self) -> &str {
+    pub fn to_default(&self) -> String {
         self.to(Lang::default())
     }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+impl Languages {
+    /// Discovers languages from the translations and validates the fallback chains
+    pub fn from_toml(src: &str) -> Result<Self, Error> {
+        let file: TextFile = toml::from_str(src).map_err(Error::ParseText)?;
+        let mut confs = file.languages;
+        let mut texts = BTreeMap::new();
+        for (key, translations) in file.texts {
+            if !translations.contains_key(DEFAULT_LANG) {
+                return Err(Error::NoDefaultTranslation(key));
+            }
+            for lang in translations.keys() {
+                confs.entry(lang.clone()).or_default();
+            }
+            texts.insert(key, translations);
+        }
+
+        let mut langs = BTreeMap::new();
+        for (key, conf) in &confs {
+            let mut chain = vec![key.clone()];
+            let mut fallback = conf.fallback.as_ref();
+            while let Some(next) = fallback {
+                if chain.contains(next) {
+                    return Err(Error::FallbackCycle(key.clone()));
+                }
+                let next_conf = confs
+                    .get(next)
+                    .ok_or_else(|| Error::UnknownFallback(key.clone(), next.clone()))?;
+                chain.push(next.clone());
+                fallback = next_conf.fallback.as_ref();
+            }
+            if !chain.iter().any(|l| l == DEFAULT_LANG) {
+                chain.push(DEFAULT_LANG.into());
+            }
+            let language = Language {
+                codes: conf.codes.clone(),
+                chain,
+            };
+            langs.insert(key.clone(), language);
+        }
+        Ok(Self { langs, texts })
+    }
+
+    /// All the supported languages
+    pub fn all(&'static self) -> impl Iterator<Item = Lang> {
+        self.langs.keys().map(|l| Lang(l))
+    }
+
+    /// Finds a language by its key or a telegram `language_code` like `pt-br`
+    pub fn find(&'static self, code: &str) -> Option<Lang> {
+        self.key(code).map(Lang)
+    }
+
+    /// The key of the language with the key or the `language_code` like `pt-br`
+    pub fn key(&self, code: &str) -> Option<&str> {
+        let code = code.to_lowercase();
+        let primary = code.split(['-', '_']).next().unwrap_or_default();
+        let found = [code.as_str(), primary].into_iter().find_map(|c| {
+            self.langs
+                .iter()
+                .find(|(key, lang)| *key == c || lang.codes.iter().any(|l| l == c))
+                .map(|(key, _)| key.as_str())
+        });
+        found
+    }
+
+    /// Text keys missing per language, these are taken from the fallbacks
+    pub fn missing(&self) -> BTreeMap<&str, Vec<&str>> {
+        let mut missing = BTreeMap::new();
+        for lang in self.langs.keys() {
+            let keys: Vec<_> = self
+                .texts
+                .iter()
+                .filter(|(_, translations)| !translations.contains_key(lang))
+                .map(|(key, _)| key.as_str())
+                .collect();
+            if !keys.is_empty() {
+                missing.insert(lang.as_str(), keys);
+            }
+        }
+        missing
+    }
+
+    /// Finds a language by its exact key
+    pub fn get(&'static self, key: &str) -> Option<Lang> {
+        self.langs.get_key_value(key).map(|(key, _)| Lang(key))
+    }
+
+    /// Keys of all the texts
+    pub fn text_keys(&self) -> impl Iterator<Item = &str> {
+        self.texts.keys().map(String::as_str)
+    }
+
+    /// The built-in text from `text.toml` without fallbacks
+    pub fn builtin(&self, key: &str, lang: &str) -> Option<&str> {
+        self.texts.get(key)?.get(lang).map(String::as_str)
+    }
+
+    /// Checks the text could replace the built-in one: it's valid HTML for `HTML_TEXTS` and has
+    /// the same number of placeholders
+    pub fn validate(&self, key: &str, text: &str) -> Result<(), TextError> {
+        let source = self
+            .texts
+            .get(key)
+            .and_then(|t| t.get(DEFAULT_LANG))
+            .ok_or_else(|| TextError::Unknown(key.into()))?;
+        if HTML_TEXTS.contains(&key) {
+            html::tags(text)?;
+        }
+        let expected = source.matches("{}").count();
+        if text.matches("{}").count() != expected {
+            return Err(TextError::Placeholders(expected));
+        }
+        Ok(())
+    }
+
+    /// Logs a warning for each incomplete translation
+    pub fn report_incomplete(&self) {
+        for (lang, keys) in self.missing() {
+            log::warn!(
+                "Incomplete `{lang}` translation, falling back to {} for: {}",
+                self.chain(lang)[1..].join(" → "),
+                keys.join(", "),
+            );
+        }
+    }
+
+    fn chain(&self, lang: &str) -> &[String] {
+        self.langs
+            .get(lang)
+            .map(|l| l.chain.as_slice())
+            .unwrap_or_default()
+    }
 }
 
 impl From<&str> for Lang {
     fn from(value: &str) -> Self {
-        match value {
-            "en" => Self::En,
-            "ru" => Self::Ru,
-            "uk" => Self::Ua,
-            _ => Self::default(),
-        }
+        LANGS.find(value).unwrap_or_default()
     }
 }
 
 impl From<String> for Lang {
     fn from(value: String) -> Self {
-        match value.as_str() {
-            "en" => Self::En,
-            "ru" => Self::Ru,
-            "uk" => Self::Ua,
-            _ => Self::default(),
-        }
+        value.as_str().into()
     }
 }
 
@@ -81,7 +338,7 @@ pub trait Translate {
 
 impl Translate for &'static Translations {
     fn translate(self, lang: Lang) -> String {
-        self.to(lang).into()
+        self.to(lang)
     }
 }
 
@@ -110,3 +367,98 @@ impl From<&Message> for Lang {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = r#"
+        [languages.be]
+        fallback = "ru"
+
+        [languages.ua]
+        codes = ["uk"]
+
+        [hello]
+        en = "Hello"
+        ru = "Привет"
+        ua = "Привіт"
+
+        [bye]
+        en = "Bye"
+        be = "Бывай"
+    "#;
+
+    #[test]
+    fn builtin_languages() {
+        assert!(!LANGS.missing().contains_key(DEFAULT_LANG));
+        assert_eq!(Lang::from("uk").code(), "ua");
+        assert_eq!(Lang::from("xx").code(), DEFAULT_LANG);
+    }
+
+    #[test]
+    fn translations_know_their_keys() {
+        assert_eq!(TEXT.help_message.key(), "help_message");
+        assert_eq!(TEXT.help.to(Lang::from("en")), "Help");
+    }
+
+    #[test]
+    fn languages_discovered() {
+        let langs: &'static _ = Box::leak(Box::new(Languages::from_toml(SRC).unwrap()));
+        let all: Vec<_> = langs.all().map(Lang::code).collect();
+        assert_eq!(all, ["be", "en", "ru", "ua"]);
+        assert_eq!(langs.find("uk").unwrap().code(), "ua");
+        assert_eq!(langs.find("be-BY").unwrap().code(), "be");
+        assert!(langs.find("kk").is_none());
+    }
+
+    #[test]
+    fn fallback_chains() {
+        let langs = Languages::from_toml(SRC).unwrap();
+        assert_eq!(langs.chain("be"), ["be", "ru", "en"]);
+        assert_eq!(langs.chain("ua"), ["ua", "en"]);
+        assert_eq!(langs.chain("en"), ["en"]);
+        assert_eq!(
+            langs.missing(),
+            BTreeMap::from([
+                ("be", vec!["hello"]),
+                ("ru", vec!["bye"]),
+                ("ua", vec!["bye"]),
+            ])
+        );
+    }
+
+    #[test]
+    fn validate_text() {
+        assert_eq!(
+            LANGS.validate("unknown", "x"),
+            Err(TextError::Unknown("unknown".into()))
+        );
+        assert!(LANGS.validate("help", "Aide").is_ok());
+        assert!(LANGS.validate("flood", "Wait {} seconds").is_ok());
+        assert_eq!(
+            LANGS.validate("flood", "Wait a bit"),
+            Err(TextError::Placeholders(1))
+        );
+        assert!(LANGS.validate("help_message", "<b>Hi</b>").is_ok());
+        assert!(LANGS.validate("help_message", "<b>Hi").is_err());
+    }
+
+    #[test]
+    fn invalid_languages() {
+        assert!(matches!(
+            Languages::from_toml("[hello]\nru = \"Привет\""),
+            Err(Error::NoDefaultTranslation(_))
+        ));
+        assert!(matches!(
+            Languages::from_toml("[languages.be]\nfallback = \"kk\""),
+            Err(Error::UnknownFallback(..))
+        ));
+        assert!(matches!(
+            Languages::from_toml(
+                "[languages.be]\nfallback = \"ru\"\n[languages.ru]\nfallback = \"be\""
+            ),
+            Err(Error::FallbackCycle(_))
+        ));
+    }
+}
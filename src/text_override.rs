@@ -0,0 +1,259 @@
+use crate::{log_error, Error, Lang, Replier, ReplyResult, CONF, DEFAULT_LANG, HTML_TEXTS, LANGS};
+use once_cell::sync::Lazy;
+use sqlx::{query, PgPool};
+use std::{collections::HashMap, fmt::Write, sync::RwLock};
+
+const TEXT_CMD: &str = "/text";
+const SET_TEXT_CMD: &str = "/set-text";
+const RESET_TEXT_CMD: &str = "/reset-text";
+
+/// Cached overrides of the built-in texts: text key → language → text
+static OVERRIDES: Lazy<RwLock<HashMap<String, HashMap<String, String>>>> =
+    Lazy::new(Default::default);
+
+/// Admin commands to view and change the bot texts without a rebuild
+#[derive(Debug, PartialEq, Eq)]
+pub enum TextCommand {
+    /// `/text` lists the texts and their overrides
+    List,
+    /// `/text <key>` shows the text in every language
+    Show(String),
+    /// `/set-text <key> <lang> <text>` overrides the text in the language
+    Set {
+        key: String,
+        lang: Lang,
+        text: String,
+    },
+    /// `/reset-text <key> <lang>` restores the built-in text
+    Reset { key: String, lang: Lang },
+}
+
+/// Returns the cached override of the text
+pub(crate) fn get(key: &str, lang: &str) -> Option<String> {
+    OVERRIDES
+        .read()
+        .expect("OVERRIDES.read")
+        .get(key)?
+        .get(lang)
+        .cloned()
+}
+
+/// Reloads the cached overrides from the database
+pub async fn load_text_overrides(pool: &PgPool) -> Result<(), Error> {
+    let rows = query!("SELECT key, lang, text FROM text_override")
+        .fetch_all(pool)
+        .await
+        .map_err(Error::LoadTextOverrides)?;
+    let mut overrides: HashMap<String, HashMap<String, String>> = HashMap::new();
+    for row in rows {
+        overrides
+            .entry(row.key)
+            .or_default()
+            .insert(row.lang, row.text);
+    }
+    *OVERRIDES.write().expect("OVERRIDES.write") = overrides;
+    Ok(())
+}
+
+/// Keeps the cache in sync with the database, e.g. after editing it with psql
+pub async fn refresh_text_overrides(pool: PgPool) {
+    loop {
+        tokio::time::sleep(CONF.text_refresh_interval).await;
+        if let Err(e) = load_text_overrides(&pool).await {
+            log_error(&e);
+        }
+    }
+}
+
+impl TextCommand {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let (name, args) = cmd.split_once(char::is_whitespace).unwrap_or((cmd, ""));
+        let (key, args) = next_arg(args);
+        match name {
+            TEXT_CMD if key.is_empty() => Some(Self::List),
+            TEXT_CMD => Some(Self::Show(key.into())),
+            SET_TEXT_CMD | RESET_TEXT_CMD => {
+                let (lang, text) = next_arg(args);
+                let lang = LANGS.get(lang)?;
+                let key = key.to_owned();
+                if name == RESET_TEXT_CMD {
+                    Some(Self::Reset { key, lang })
+                } else if !text.is_empty() {
+                    let text = text.into();
+                    Some(Self::Set { key, lang, text })
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        match self {
+            Self::List => {
+                let mut msg = String::from("Texts:\n");
+                for key in LANGS.text_keys() {
+                    let langs = overridden_langs(key);
+                    if langs.is_empty() {
+                        writeln!(msg, "{key}").ok();
+                    } else {
+                        writeln!(msg, "{key} (overridden: {})", langs.join(", ")).ok();
+                    }
+                }
+                write!(
+                    msg,
+                    "\n{TEXT_CMD} <key> - show a text\n\
+                    {SET_TEXT_CMD} <key> <lang> <text> - override a text\n\
+                    {RESET_TEXT_CMD} <key> <lang> - restore a built-in text"
+                )
+                .ok();
+                repl.send_text(msg).await?;
+            }
+            Self::Show(key) => {
+                if !is_known(key) {
+                    repl.send_text(format!("❌ Unknown text {key}")).await?;
+                    return Ok(());
+                }
+                for lang in LANGS.all() {
+                    let (source, text) = if let Some(text) = get(key, lang.code()) {
+                        ("override", text)
+                    } else if let Some(text) = LANGS.builtin(key, lang.code()) {
+                        ("built-in", text.to_owned())
+                    } else {
+                        ("missing, using fallbacks", String::new())
+                    };
+                    repl.send_text(format!("{key} [{}] {source}:\n\n{text}", lang.code()))
+                        .await?;
+                }
+            }
+            Self::Set { key, lang, text } => {
+                if let Err(e) = LANGS.validate(key, text) {
+                    repl.send_text(format!("❌ {e}")).await?;
+                    return Ok(());
+                }
+                let res = query!(
+                    r#"
+                    INSERT INTO text_override (key, lang, text) VALUES ($1, $2, $3)
+                    ON CONFLICT (key, lang) DO UPDATE SET text = $3, updated_at = now()
+                    "#,
+                    key,
+                    lang.code(),
+                    text,
+                )
+                .execute(pool)
+                .await
+                .map_err(|e| Error::SetTextOverride(e, key.clone(), *lang));
+                match res {
+                    Ok(_) => {
+                        let pending = reload(pool).await;
+                        repl.send_text(format!("✅ {key} [{}] updated{pending}:", lang.code()))
+                            .await?;
+                        if HTML_TEXTS.contains(&key.as_str()) {
+                            repl.send_html(text.as_str()).await?;
+                        } else {
+                            repl.send_text(text.as_str()).await?;
+                        }
+                    }
+                    Err(e) => {
+                        log_error(&e);
+                        repl.send_text(format!("❌ {e}")).await?;
+                    }
+                }
+            }
+            Self::Reset { key, lang } => {
+                let res = query!(
+                    "DELETE FROM text_override WHERE key = $1 AND lang = $2",
+                    key,
+                    lang.code(),
+                )
+                .execute(pool)
+                .await
+                .map_err(|e| Error::ResetTextOverride(e, key.clone(), *lang));
+                match res {
+                    Ok(done) if done.rows_affected() == 0 => {
+                        repl.send_text(format!("{key} [{}] isn't overridden", lang.code()))
+                            .await?;
+                    }
+                    Ok(_) => {
+                        let pending = reload(pool).await;
+                        repl.send_text(format!("✅ {key} [{}] reset{pending}", lang.code()))
+                            .await?;
+                    }
+                    Err(e) => {
+                        log_error(&e);
+                        repl.send_text(format!("❌ {e}")).await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reloads the overrides after a change, if that fails the change waits for the periodic
+/// refresh and the returned note says so
+async fn reload(pool: &PgPool) -> &'static str {
+    match load_text_overrides(pool).await {
+        Ok(()) => "",
+        Err(e) => {
+            log_error(&e);
+            ", will apply after the next refresh"
+        }
+    }
+}
+
+fn is_known(key: &str) -> bool {
+    LANGS.builtin(key, DEFAULT_LANG).is_some()
+}
+
+fn overridden_langs(key: &str) -> Vec<String> {
+    let overrides = OVERRIDES.read().expect("OVERRIDES.read");
+    let mut langs: Vec<_> = overrides
+        .get(key)
+        .map(|langs| langs.keys().cloned().collect())
+        .unwrap_or_default();
+    langs.sort();
+    langs
+}
+
+/// Splits off the next whitespace separated argument
+fn next_arg(args: &str) -> (&str, &str) {
+    let args = args.trim_start();
+    let (arg, rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+    (arg, rest.trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_command_from_command() {
+        assert!(TextCommand::from_command("/unknown").is_none());
+        assert!(TextCommand::from_command("/texts").is_none());
+        assert_eq!(TextCommand::from_command("/text"), Some(TextCommand::List));
+        assert_eq!(
+            TextCommand::from_command("/text help"),
+            Some(TextCommand::Show("help".into()))
+        );
+        assert!(TextCommand::from_command("/set-text help").is_none());
+        assert!(TextCommand::from_command("/set-text help xx Help").is_none());
+        assert!(TextCommand::from_command("/set-text help en").is_none());
+        assert_eq!(
+            TextCommand::from_command("/set-text help_message ru\n<b>Привет</b>\nмир"),
+            Some(TextCommand::Set {
+                key: "help_message".into(),
+                lang: Lang::from("ru"),
+                text: "<b>Привет</b>\nмир".into(),
+            })
+        );
+        assert_eq!(
+            TextCommand::from_command("/reset-text help ua"),
+            Some(TextCommand::Reset {
+                key: "help".into(),
+                lang: Lang::from("uk"),
+            })
+        );
+    }
+}
@@ -0,0 +1,554 @@
+use crate::{html, Error, Languages, TextError, DEFAULT_LANG, HTML_TEXTS};
+use quick_xml::{escape::escape, events::Event, Reader};
+use std::{collections::BTreeMap, fmt, fmt::Write, path::Path};
+use strum_macros::{AsRefStr, EnumString};
+
+/// File formats to exchange translations with translators
+#[derive(Clone, Copy, Debug, PartialEq, Eq, EnumString, AsRefStr)]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    /// gettext PO
+    Po,
+    /// XLIFF 1.2
+    Xliff,
+}
+
+/// Translations to a language read from a translator's file
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Catalog {
+    pub lang: String,
+    /// Text key → entry
+    pub entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    /// The english text the translation was made from
+    pub source: String,
+    /// The translation, empty if missing
+    pub target: String,
+}
+
+/// A catalog checked against the current texts
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub lang: String,
+    /// Valid translations: text key → text
+    pub translated: BTreeMap<String, String>,
+    /// Texts without a translation
+    pub missing: Vec<String>,
+    /// Unknown texts and ones with the source changed since the export
+    pub stale: Vec<String>,
+    /// Translations failed the validation
+    pub invalid: Vec<(String, TextError)>,
+}
+
+impl Format {
+    /// Guesses the format by the file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "po" => Some(Self::Po),
+            "xlf" | "xliff" => Some(Self::Xliff),
+            _ => None,
+        }
+    }
+
+    /// Exports the texts with the english source and the current translation to the language,
+    /// a `language_code` like `uk` exports the language serving it
+    pub fn export(self, langs: &Languages, lang: &str) -> String {
+        let lang = langs.key(lang).unwrap_or(lang);
+        let mut out = String::new();
+        match self {
+            Self::Po => {
+                writeln!(out, "# War Lessons bot texts").ok();
+                writeln!(out, "msgid \"\"").ok();
+                writeln!(out, "msgstr \"\"").ok();
+                writeln!(out, "\"Project-Id-Version: war-lessons-bot\\n\"").ok();
+                writeln!(out, "\"Language: {lang}\\n\"").ok();
+                writeln!(out, "\"MIME-Version: 1.0\\n\"").ok();
+                writeln!(out, "\"Content-Type: text/plain; charset=UTF-8\\n\"").ok();
+                writeln!(out, "\"Content-Transfer-Encoding: 8bit\\n\"").ok();
+                for key in langs.text_keys() {
+                    let source = langs.builtin(key, DEFAULT_LANG).unwrap_or_default();
+                    let target = langs.builtin(key, lang).unwrap_or_default();
+                    writeln!(out).ok();
+                    for note in notes(key, source) {
+                        writeln!(out, "#. {note}").ok();
+                    }
+                    writeln!(out, "msgctxt {}", po_string(key)).ok();
+                    writeln!(out, "msgid {}", po_string(source)).ok();
+                    writeln!(out, "msgstr {}", po_string(target)).ok();
+                }
+            }
+            Self::Xliff => {
+                writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).ok();
+                writeln!(
+                    out,
+                    r#"<xliff version="1.2" xmlns="urn:oasis:names:tc:xliff:document:1.2">"#
+                )
+                .ok();
+                writeln!(
+                    out,
+                    r#"  <file original="text.toml" datatype="plaintext" source-language="{DEFAULT_LANG}" target-language="{}">"#,
+                    escape(lang)
+                )
+                .ok();
+                writeln!(out, "    <body>").ok();
+                for key in langs.text_keys() {
+                    let source = langs.builtin(key, DEFAULT_LANG).unwrap_or_default();
+                    let target = langs.builtin(key, lang).unwrap_or_default();
+                    writeln!(
+                        out,
+                        r#"      <trans-unit id="{0}" resname="{0}" xml:space="preserve">"#,
+                        escape(key)
+                    )
+                    .ok();
+                    writeln!(out, "        <source>{}</source>", escape(source)).ok();
+                    writeln!(out, "        <target>{}</target>", escape(target)).ok();
+                    for note in notes(key, source) {
+                        writeln!(out, "        <note>{note}</note>").ok();
+                    }
+                    writeln!(out, "      </trans-unit>").ok();
+                }
+                writeln!(out, "    </body>").ok();
+                writeln!(out, "  </file>").ok();
+                writeln!(out, "</xliff>").ok();
+            }
+        }
+        out
+    }
+
+    /// Reads a translator's file
+    pub fn parse(self, src: &str) -> Result<Catalog, Error> {
+        let catalog = match self {
+            Self::Po => parse_po(src)?,
+            Self::Xliff => parse_xliff(src)?,
+        };
+        if catalog.lang.is_empty() {
+            return Err(Error::NoCatalogLanguage);
+        }
+        Ok(catalog)
+    }
+}
+
+impl Catalog {
+    /// Validates the translations against the current texts. The catalog language may be a
+    /// `language_code` of a known language, unknown ones are refused.
+    pub fn check(&self, langs: &Languages) -> Result<ImportReport, Error> {
+        let lang = langs
+            .key(&self.lang)
+            .ok_or_else(|| Error::UnknownCatalogLanguage(self.lang.clone()))?;
+        let mut report = ImportReport {
+            lang: lang.into(),
+            ..Default::default()
+        };
+        for key in langs.text_keys() {
+            let source = langs.builtin(key, DEFAULT_LANG).unwrap_or_default();
+            match self.entries.get(key) {
+                Some(entry) if entry.target.is_empty() => report.missing.push(key.into()),
+                Some(entry) if entry.source != source => report.stale.push(key.into()),
+                Some(entry) => match check_translation(langs, key, source, &entry.target) {
+                    Ok(()) => {
+                        report.translated.insert(key.into(), entry.target.clone());
+                    }
+                    Err(e) => report.invalid.push((key.into(), e)),
+                },
+                None => report.missing.push(key.into()),
+            }
+        }
+        for key in self.entries.keys() {
+            if langs.builtin(key, DEFAULT_LANG).is_none() {
+                report.stale.push(key.clone());
+            }
+        }
+        Ok(report)
+    }
+}
+
+impl ImportReport {
+    /// Writes the valid translations into `text.toml` keeping its formatting
+    pub fn apply(&self, text_toml: &str) -> Result<String, Error> {
+        let mut doc: toml_edit::Document = text_toml.parse().map_err(Error::EditText)?;
+        for (key, text) in &self.translated {
+            doc[key][&self.lang] = toml_edit::value(text.as_str());
+        }
+        Ok(doc.to_string())
+    }
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} translated, {} missing, {} stale, {} invalid",
+            self.lang,
+            self.translated.len(),
+            self.missing.len(),
+            self.stale.len(),
+            self.invalid.len(),
+        )?;
+        if !self.missing.is_empty() {
+            writeln!(f, "missing: {}", self.missing.join(", "))?;
+        }
+        if !self.stale.is_empty() {
+            writeln!(f, "stale: {}", self.stale.join(", "))?;
+        }
+        for (key, e) in &self.invalid {
+            writeln!(f, "invalid {key}: {e}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Hints for translators
+fn notes(key: &str, source: &str) -> Vec<&'static str> {
+    let mut notes = vec![];
+    if HTML_TEXTS.contains(&key) {
+        notes.push("Telegram HTML, keep the same tags");
+    }
+    if source.contains("{}") {
+        notes.push("Keep the {} placeholders");
+    }
+    notes
+}
+
+/// Validates the translation and checks the HTML tags are preserved
+fn check_translation(
+    langs: &Languages,
+    key: &str,
+    source: &str,
+    target: &str,
+) -> Result<(), TextError> {
+    langs.validate(key, target)?;
+    if HTML_TEXTS.contains(&key) {
+        let expected = html::tags(source)?;
+        let tags = html::tags(target)?;
+        if tags != expected {
+            return Err(TextError::Tags(format!(
+                "expected {}, got {}",
+                expected.join(" "),
+                tags.join(" ")
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Formats a PO string, splitting multiline ones
+fn po_string(s: &str) -> String {
+    let escape = |s: &str| {
+        s.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\t', "\\t")
+            .replace('\r', "\\r")
+            .replace('\n', "\\n")
+    };
+    if !s.contains('\n') {
+        return format!("\"{}\"", escape(s));
+    }
+    let mut out = String::from("\"\"");
+    for line in s.split_inclusive('\n') {
+        write!(out, "\n\"{}\"", escape(line)).ok();
+    }
+    out
+}
+
+/// Parses a quoted PO string
+fn po_unquote(s: &str) -> Option<String> {
+    let s = s.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                'n' => out.push('\n'),
+                't' => out.push('\t'),
+                'r' => out.push('\r'),
+                c @ ('"' | '\\') => out.push(c),
+                _ => return None,
+            }
+        } else if c == '"' {
+            return None;
+        } else {
+            out.push(c);
+        }
+    }
+    Some(out)
+}
+
+#[derive(Default)]
+struct PoEntry {
+    ctxt: Option<String>,
+    id: String,
+    str: Option<String>,
+    fuzzy: bool,
+}
+
+fn parse_po(src: &str) -> Result<Catalog, Error> {
+    let mut catalog = Catalog::default();
+    let mut entry = PoEntry::default();
+    let mut field: Option<&str> = None;
+    for (n, line) in src.lines().enumerate() {
+        let line = line.trim();
+        let err = || Error::ParsePo(n + 1);
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(flags) = line.strip_prefix("#,") {
+            finish_po_entry(&mut catalog, &mut entry);
+            entry.fuzzy = flags.split(',').any(|f| f.trim() == "fuzzy");
+            continue;
+        }
+        if line.starts_with('#') {
+            continue;
+        }
+        let (keyword, value) = if line.starts_with('"') {
+            (field.ok_or_else(err)?, line)
+        } else {
+            let (keyword, value) = line.split_once(char::is_whitespace).ok_or_else(err)?;
+            if keyword != "msgstr" {
+                finish_po_entry(&mut catalog, &mut entry);
+            }
+            (keyword, value)
+        };
+        let value = po_unquote(value).ok_or_else(err)?;
+        match keyword {
+            "msgctxt" => entry.ctxt.get_or_insert_with(String::new).push_str(&value),
+            "msgid" => entry.id.push_str(&value),
+            "msgstr" => entry.str.get_or_insert_with(String::new).push_str(&value),
+            _ => return Err(err()),
+        }
+        field = Some(match keyword {
+            "msgctxt" => "msgctxt",
+            "msgid" => "msgid",
+            _ => "msgstr",
+        });
+    }
+    finish_po_entry(&mut catalog, &mut entry);
+    Ok(catalog)
+}
+
+/// Adds the entry to the catalog once it's complete, i.e. has a `msgstr`
+fn finish_po_entry(catalog: &mut Catalog, entry: &mut PoEntry) {
+    if entry.str.is_none() {
+        return;
+    }
+    let entry = std::mem::take(entry);
+    let target = entry.str.unwrap_or_default();
+    match entry.ctxt {
+        None if entry.id.is_empty() => {
+            if let Some(lang) = target.lines().find_map(|l| l.strip_prefix("Language:")) {
+                catalog.lang = lang.trim().into();
+            }
+        }
+        ctxt => {
+            let key = ctxt.unwrap_or_else(|| entry.id.clone());
+            let target = if entry.fuzzy { String::new() } else { target };
+            let source = entry.id;
+            catalog.entries.insert(key, Entry { source, target });
+        }
+    }
+}
+
+fn parse_xliff(src: &str) -> Result<Catalog, Error> {
+    let mut catalog = Catalog::default();
+    let mut reader = Reader::from_str(src);
+    let mut key = None;
+    let mut entry = Entry::default();
+    // Whether we're inside `<source>` or `<target>`
+    let mut field = None;
+    loop {
+        match reader.read_event().map_err(Error::ParseXliff)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"file" => {
+                    if let Some(lang) = e
+                        .try_get_attribute("target-language")
+                        .map_err(Error::ParseXliff)?
+                    {
+                        catalog.lang = lang.unescape_value().map_err(Error::ParseXliff)?.into();
+                    }
+                }
+                b"trans-unit" => {
+                    if let Some(id) = e.try_get_attribute("id").map_err(Error::ParseXliff)? {
+                        key = Some(id.unescape_value().map_err(Error::ParseXliff)?.into_owned());
+                    }
+                }
+                name @ (b"source" | b"target") => field = Some(name == b"source"),
+                _ => (),
+            },
+            Event::Text(e) => {
+                let text = e.unescape().map_err(Error::ParseXliff)?;
+                match field {
+                    Some(true) => entry.source.push_str(&text),
+                    Some(false) => entry.target.push_str(&text),
+                    None => (),
+                }
+            }
+            Event::CData(e) => {
+                let text = String::from_utf8_lossy(&e);
+                match field {
+                    Some(true) => entry.source.push_str(&text),
+                    Some(false) => entry.target.push_str(&text),
+                    None => (),
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"source" | b"target" => field = None,
+                b"trans-unit" => {
+                    field = None;
+                    let entry = std::mem::take(&mut entry);
+                    if let Some(key) = key.take() {
+                        catalog.entries.insert(key, entry);
+                    }
+                }
+                _ => (),
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    Ok(catalog)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = r#"
+        [greeting]
+        en = "Hello\n\"friend\""
+        ru = "Привет"
+
+        [help_message]
+        en = "<b>Help</b> me"
+
+        [flood]
+        en = "Wait {} seconds"
+    "#;
+
+    fn catalog(entries: &[(&str, &str, &str)]) -> Catalog {
+        Catalog {
+            lang: "ru".into(),
+            entries: entries
+                .iter()
+                .map(|(key, source, target)| {
+                    let source = source.to_string();
+                    let target = target.to_string();
+                    (key.to_string(), Entry { source, target })
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let langs = Languages::from_toml(SRC).unwrap();
+        let expected = catalog(&[
+            ("flood", "Wait {} seconds", ""),
+            ("greeting", "Hello\n\"friend\"", "Привет"),
+            ("help_message", "<b>Help</b> me", ""),
+        ]);
+        for format in [Format::Po, Format::Xliff] {
+            let exported = format.export(&langs, "ru");
+            assert_eq!(format.parse(&exported).unwrap(), expected, "{exported}");
+        }
+    }
+
+    #[test]
+    fn parse_po_file() {
+        let po = r#"
+msgid ""
+msgstr ""
+"Language: be\n"
+
+#, fuzzy
+msgctxt "greeting"
+msgid "Hello"
+msgstr "Прывітанне"
+
+msgctxt "flood"
+msgid "Wait {} seconds"
+msgstr ""
+"Пачакайце "
+"{} секунд"
+"#;
+        let catalog = Format::Po.parse(po).unwrap();
+        assert_eq!(catalog.lang, "be");
+        assert_eq!(catalog.entries["greeting"].target, "");
+        assert_eq!(catalog.entries["flood"].target, "Пачакайце {} секунд");
+        assert!(matches!(
+            Format::Po.parse("msgid \"x"),
+            Err(Error::ParsePo(1))
+        ));
+        assert!(matches!(
+            Format::Po.parse("msgid \"\"\nmsgstr \"\""),
+            Err(Error::NoCatalogLanguage)
+        ));
+    }
+
+    #[test]
+    fn check_catalog() {
+        let langs = Languages::from_toml(SRC).unwrap();
+        let report = catalog(&[
+            ("flood", "Wait {} seconds", "Подождите"),
+            ("greeting", "Hi", "Привет"),
+            ("help_message", "<b>Help</b> me", "<i>Помогите</i> мне"),
+            ("removed", "Bye", "Пока"),
+        ])
+        .check(&langs)
+        .unwrap();
+        assert!(report.translated.is_empty());
+        assert!(report.missing.is_empty());
+        assert_eq!(report.stale, ["greeting", "removed"]);
+        assert_eq!(
+            report.invalid,
+            [
+                ("flood".into(), TextError::Placeholders(1)),
+                (
+                    "help_message".into(),
+                    TextError::Tags("expected b /b, got i /i".into())
+                ),
+            ]
+        );
+
+        let report = catalog(&[
+            ("flood", "Wait {} seconds", "Подождите {} секунд"),
+            ("help_message", "<b>Help</b> me", "<b>Помогите</b> мне"),
+        ])
+        .check(&langs)
+        .unwrap();
+        assert_eq!(report.translated.len(), 2);
+        assert_eq!(report.missing, ["greeting"]);
+    }
+
+    #[test]
+    fn apply_report() {
+        let langs = Languages::from_toml(SRC).unwrap();
+        let report = catalog(&[
+            ("flood", "Wait {} seconds", "Подождите\n{} секунд"),
+            ("greeting", "Hello\n\"friend\"", "Здравствуй"),
+        ])
+        .check(&langs)
+        .unwrap();
+        let applied = report.apply(SRC).unwrap();
+        let langs = Languages::from_toml(&applied).unwrap();
+        assert_eq!(langs.builtin("flood", "ru"), Some("Подождите\n{} секунд"));
+        assert_eq!(langs.builtin("greeting", "ru"), Some("Здравствуй"));
+        assert_eq!(langs.builtin("greeting", "en"), Some("Hello\n\"friend\""));
+    }
+
+    #[test]
+    fn catalog_language_codes() {
+        let langs =
+            Languages::from_toml(&format!("{SRC}[languages.ua]\ncodes = [\"uk\"]")).unwrap();
+        let mut uk = catalog(&[("greeting", "Hello\n\"friend\"", "Привіт")]);
+        uk.lang = "uk-UA".into();
+        assert_eq!(uk.check(&langs).unwrap().lang, "ua");
+        assert!(Format::Po.export(&langs, "uk").contains("Language: ua"));
+        uk.lang = "kk".into();
+        assert!(matches!(
+            uk.check(&langs),
+            Err(Error::UnknownCatalogLanguage(lang)) if lang == "kk"
+        ));
+    }
+}
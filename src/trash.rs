@@ -0,0 +1,322 @@
+use crate::{
+    internal_error, lesson::timeago, log_error, util::preview, Error, Replier, ReplyResult, CONF,
+};
+use sqlx::{query, PgPool};
+use std::{fmt::Write, time::Duration};
+use teloxide::{
+    payloads::SendMessageSetters,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
+
+const DELETE_CMD: &str = "/delete";
+const RESTORE_CMD: &str = "/restore";
+const TRASH_CMD: &str = "/trash";
+/// Lessons listed in the trash, the most recently deleted first
+const TRASH_PAGE_SIZE: i64 = 20;
+const TEXT_PREVIEW_LEN: usize = 60;
+/// How often to purge the lessons deleted longer than `trash_retention` ago
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Admins' commands to remove lessons, every action is recorded in `lesson_deletion`
+#[derive(Debug, PartialEq, Eq)]
+pub enum TrashCommand {
+    /// `/delete <lesson id> [reason]` moves the lesson to the trash
+    Delete {
+        lesson_id: i32,
+        reason: Option<String>,
+    },
+    /// `/restore <lesson id>` takes the lesson out of the trash with its status
+    Restore(i32),
+    /// `/trash` lists the deleted lessons
+    List,
+    /// `/trash <lesson id>` shows who deleted, restored or purged the lesson and when
+    History(i32),
+}
+
+impl TrashCommand {
+    pub fn from_command(cmd: &str) -> Option<Self> {
+        let mut parts = cmd.split_whitespace();
+        let name = parts.next()?;
+        let lesson_id = parts.next().map(str::parse::<i32>);
+        let rest = parts.collect::<Vec<_>>();
+        match (name, lesson_id) {
+            (DELETE_CMD, Some(Ok(lesson_id))) => Some(Self::Delete {
+                lesson_id,
+                reason: (!rest.is_empty()).then(|| rest.join(" ")),
+            }),
+            (RESTORE_CMD, Some(Ok(lesson_id))) if rest.is_empty() => Some(Self::Restore(lesson_id)),
+            (TRASH_CMD, None) => Some(Self::List),
+            (TRASH_CMD, Some(Ok(lesson_id))) if rest.is_empty() => Some(Self::History(lesson_id)),
+            _ => None,
+        }
+    }
+
+    pub async fn reply(&self, pool: &PgPool, repl: &Replier) -> ReplyResult {
+        match self {
+            Self::Delete { lesson_id, reason } => {
+                match delete(pool, *lesson_id, reason.as_deref(), repl.sender_id()).await {
+                    Ok(true) => {
+                        repl.send_text(format!(
+                            "🗑 Lesson {lesson_id} is in the trash and will be purged in {}, \
+                            {RESTORE_CMD} {lesson_id} brings it back",
+                            humantime::format_duration(CONF.trash_retention)
+                        ))
+                        .await?
+                    }
+                    Ok(false) => {
+                        repl.send_text(format!(
+                            "Lesson {lesson_id} doesn't exist or is already in the trash"
+                        ))
+                        .await?
+                    }
+                    Err(e) => repl.send_text(internal_error(&e)).await?,
+                };
+            }
+            Self::Restore(lesson_id) => {
+                match restore(pool, *lesson_id, repl.sender_id()).await {
+                    Ok(true) => {
+                        repl.send_text(format!("♻️ Lesson {lesson_id} is restored"))
+                            .await?
+                    }
+                    Ok(false) => {
+                        repl.send_text(format!("Lesson {lesson_id} isn't in the trash"))
+                            .await?
+                    }
+                    Err(e) => repl.send_text(internal_error(&e)).await?,
+                };
+            }
+            Self::List => {
+                let res = query!(
+                    r#"
+                    SELECT
+                        id,
+                        text,
+                        deleted_at as "deleted_at!",
+                        count(*) OVER () as "total!"
+                    FROM lesson
+                    WHERE deleted_at IS NOT NULL
+                    ORDER BY deleted_at DESC, id DESC
+                    LIMIT $1
+                    "#,
+                    TRASH_PAGE_SIZE,
+                )
+                .fetch_all(pool)
+                .await
+                .map_err(Error::ListTrash);
+                let lessons = match res {
+                    Ok(lessons) if lessons.is_empty() => {
+                        repl.send_text("🗑 The trash is empty").await?;
+                        return Ok(());
+                    }
+                    Ok(lessons) => lessons,
+                    Err(e) => {
+                        repl.send_text(internal_error(&e)).await?;
+                        return Ok(());
+                    }
+                };
+                let mut text = format!("🗑 {} lessons in the trash:", lessons[0].total);
+                let mut lines = vec![];
+                for lesson in &lessons {
+                    write!(
+                        text,
+                        "\n\n#{} deleted {} ago, purged on {}\n{}",
+                        lesson.id,
+                        timeago(lesson.deleted_at),
+                        (lesson.deleted_at + CONF.trash_retention).date(),
+                        preview(&lesson.text, TEXT_PREVIEW_LEN),
+                    )
+                    .ok();
+                    lines.push(InlineKeyboardButton::callback(
+                        format!("♻️ {}", lesson.id),
+                        Self::Restore(lesson.id).to_command(),
+                    ));
+                }
+                repl.send_text(text)
+                    .reply_markup(InlineKeyboardMarkup::new(
+                        lines.chunks(5).map(<[_]>::to_vec),
+                    ))
+                    .await?;
+            }
+            Self::History(lesson_id) => {
+                let res = query!(
+                    r#"
+                    SELECT action, admin, reason, done_at
+                    FROM lesson_deletion
+                    WHERE lesson_id = $1
+                    ORDER BY id
+                    "#,
+                    lesson_id,
+                )
+                .fetch_all(pool)
+                .await
+                .map_err(|e| Error::DeletionHistory(e, *lesson_id));
+                match res {
+                    Ok(rows) if rows.is_empty() => {
+                        repl.send_text(format!("Lesson {lesson_id} has never been deleted"))
+                            .await?
+                    }
+                    Ok(rows) => {
+                        let mut text = format!("Lesson {lesson_id}:");
+                        for row in rows {
+                            write!(text, "\n{} {}", row.done_at.date(), row.action).ok();
+                            if let Some(admin) = row.admin {
+                                write!(text, " by {admin}").ok();
+                            } else {
+                                text.push_str(" after the retention period");
+                            }
+                            if let Some(reason) = row.reason {
+                                write!(text, ": {reason}").ok();
+                            }
+                        }
+                        repl.send_text(text).await?
+                    }
+                    Err(e) => repl.send_text(internal_error(&e)).await?,
+                };
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_command(&self) -> String {
+        match self {
+            Self::Delete {
+                lesson_id,
+                reason: Some(reason),
+            } => format!("{DELETE_CMD} {lesson_id} {reason}"),
+            Self::Delete { lesson_id, .. } => format!("{DELETE_CMD} {lesson_id}"),
+            Self::Restore(lesson_id) => format!("{RESTORE_CMD} {lesson_id}"),
+            Self::List => TRASH_CMD.to_owned(),
+            Self::History(lesson_id) => format!("{TRASH_CMD} {lesson_id}"),
+        }
+    }
+}
+
+/// Moves the lesson to the trash, returns `false` if it's missing or already there
+async fn delete(
+    pool: &PgPool,
+    lesson_id: i32,
+    reason: Option<&str>,
+    admin: Option<i64>,
+) -> Result<bool, Error> {
+    query!(
+        r#"
+        WITH l AS (
+            UPDATE lesson
+            SET deleted_at = now(), deleted_by = $2, claimed_by = NULL, claimed_at = NULL
+            WHERE id = $1 AND deleted_at IS NULL
+            RETURNING id
+        )
+        INSERT INTO lesson_deletion (lesson_id, action, admin, reason)
+        SELECT id, 'delete', $2, $3 FROM l
+        "#,
+        lesson_id,
+        admin,
+        reason,
+    )
+    .execute(pool)
+    .await
+    .map(|done| done.rows_affected() > 0)
+    .map_err(|e| Error::DeleteLesson(e, lesson_id))
+}
+
+/// Takes the lesson out of the trash, returns `false` if it isn't there
+async fn restore(pool: &PgPool, lesson_id: i32, admin: Option<i64>) -> Result<bool, Error> {
+    query!(
+        r#"
+        WITH l AS (
+            UPDATE lesson
+            SET deleted_at = NULL, deleted_by = NULL
+            WHERE id = $1 AND deleted_at IS NOT NULL
+            RETURNING id
+        )
+        INSERT INTO lesson_deletion (lesson_id, action, admin)
+        SELECT id, 'restore', $2 FROM l
+        "#,
+        lesson_id,
+        admin,
+    )
+    .execute(pool)
+    .await
+    .map(|done| done.rows_affected() > 0)
+    .map_err(|e| Error::RestoreLesson(e, lesson_id))
+}
+
+/// Deletes the lessons that have been in the trash for `trash_retention` for good, their
+/// data is gone but `lesson_deletion` keeps the purge date
+pub async fn purge_trash(pool: PgPool) {
+    loop {
+        let res = query!(
+            r#"
+            WITH purged AS (
+                DELETE FROM lesson
+                WHERE deleted_at < now() - $1::float8 * interval '1 second'
+                RETURNING id
+            )
+            INSERT INTO lesson_deletion (lesson_id, action)
+            SELECT id, 'purge' FROM purged
+            "#,
+            CONF.trash_retention.as_secs_f64(),
+        )
+        .execute(&pool)
+        .await
+        .map_err(Error::PurgeTrash);
+        match res {
+            Ok(done) if done.rows_affected() > 0 => {
+                log::info!("Purged {} lessons from the trash", done.rows_affected())
+            }
+            Ok(_) => {}
+            Err(e) => log_error(&e),
+        }
+        tokio::time::sleep(PURGE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trash_command_from_command() {
+        assert_eq!(
+            TrashCommand::from_command("/delete 12"),
+            Some(TrashCommand::Delete {
+                lesson_id: 12,
+                reason: None
+            })
+        );
+        assert_eq!(
+            TrashCommand::from_command("/delete 12 personal  data"),
+            Some(TrashCommand::Delete {
+                lesson_id: 12,
+                reason: Some("personal data".to_owned())
+            })
+        );
+        assert_eq!(
+            TrashCommand::from_command("/restore 12"),
+            Some(TrashCommand::Restore(12))
+        );
+        assert_eq!(
+            TrashCommand::from_command("/trash"),
+            Some(TrashCommand::List)
+        );
+        assert_eq!(
+            TrashCommand::from_command("/trash 12"),
+            Some(TrashCommand::History(12))
+        );
+        assert!(TrashCommand::from_command("/delete").is_none());
+        assert!(TrashCommand::from_command("/delete x").is_none());
+        assert!(TrashCommand::from_command("/restore 1 2").is_none());
+        assert!(TrashCommand::from_command("/trash all").is_none());
+        for cmd in [
+            TrashCommand::Delete {
+                lesson_id: 1,
+                reason: Some("legal request".to_owned()),
+            },
+            TrashCommand::Restore(1),
+            TrashCommand::List,
+            TrashCommand::History(1),
+        ] {
+            assert_eq!(TrashCommand::from_command(&cmd.to_command()), Some(cmd));
+        }
+    }
+}
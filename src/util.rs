@@ -0,0 +1,42 @@
+/// Makes the text match literally in `ILIKE`
+pub(crate) fn escape_like(text: &str) -> String {
+    text.replace('\\', r"\\")
+        .replace('%', r"\%")
+        .replace('_', r"\_")
+}
+
+pub(crate) fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_owned(),
+    }
+}
+
+/// The text on one line and truncated, for lists of lessons
+pub(crate) fn preview(text: &str, max_chars: usize) -> String {
+    truncate(
+        &text.split_whitespace().collect::<Vec<_>>().join(" "),
+        max_chars,
+    )
+}
+
+pub(crate) fn join_ids(ids: &[i32]) -> String {
+    ids.iter()
+        .map(i32::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_and_truncate() {
+        assert_eq!(escape_like(r"100% a_b\c"), r"100\% a\_b\\c");
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("Привет мир", 6), "Привет…");
+        assert_eq!(preview("Line one\n\n  line two", 12), "Line one lin…");
+        assert_eq!(join_ids(&[3, 1]), "3, 1");
+    }
+}